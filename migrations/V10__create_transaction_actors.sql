@@ -0,0 +1,25 @@
+CREATE TYPE transaction_actor_role as ENUM (
+        'payer',
+        'payee',
+        'owner',
+        'gateway',
+        'challenger',
+        'challengee',
+        'witness',
+        'sc_opener',
+        'sc_closer',
+        'packet_receiver'
+);
+
+CREATE TABLE transaction_actors (
+       actor TEXT NOT NULL,
+       actor_role transaction_actor_role NOT NULL,
+       transaction_hash TEXT NOT NULL,
+       block BIGINT NOT NULL,
+
+       PRIMARY KEY (actor, actor_role, transaction_hash)
+);
+
+CREATE INDEX transaction_actors_actor_block_idx on transaction_actors(actor, block);
+CREATE INDEX transaction_actors_transaction_hash_idx on transaction_actors(transaction_hash);
+CREATE INDEX transaction_actors_block_idx on transaction_actors(block);
@@ -0,0 +1,9 @@
+-- added_height: follower height when the filter was first picked up. The follower loads
+--               everything above it, the backfill loads first_block up to it.
+-- backfill_height: last height the backfill has loaded for the filter.
+ALTER TABLE filters
+       ADD COLUMN added_height BIGINT,
+       ADD COLUMN backfill_height BIGINT;
+
+-- filters that existed before this migration don't need a backfill
+UPDATE filters SET added_height = 0, backfill_height = 0;
@@ -0,0 +1,13 @@
+ALTER TYPE filter_type ADD VALUE 'owner';
+
+-- every ownership change seen on chain; the current owner of a gateway is its latest row
+CREATE TABLE gateway_owners (
+       block BIGINT NOT NULL,
+       transaction_hash TEXT NOT NULL,
+       gateway TEXT NOT NULL,
+       owner TEXT NOT NULL
+);
+
+CREATE INDEX gateway_owners_gateway_block_idx on gateway_owners(gateway, block);
+CREATE INDEX gateway_owners_owner_idx on gateway_owners(owner);
+CREATE INDEX gateway_owners_block_idx on gateway_owners(block);
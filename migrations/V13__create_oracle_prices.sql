@@ -0,0 +1,18 @@
+-- every price_oracle_v1 report seen on chain, in 1/100000000 USD per HNT. These are
+-- raw reports, a block can have several.
+CREATE TABLE oracle_prices (
+       block BIGINT NOT NULL,
+       transaction_hash TEXT NOT NULL,
+       public_key TEXT NOT NULL,
+       price BIGINT NOT NULL,
+       time BIGINT
+);
+
+CREATE INDEX oracle_prices_block_idx on oracle_prices(block);
+
+-- reports already loaded as jsonb in full mode, without a time when their block
+-- wasn't recorded
+INSERT INTO oracle_prices (block, transaction_hash, public_key, price, time)
+SELECT t.block, t.hash, t.fields->>'public_key', (t.fields->>'price')::bigint, b.time
+FROM transactions t LEFT JOIN blocks b ON b.height = t.block
+WHERE t.type = 'price_oracle_v1';
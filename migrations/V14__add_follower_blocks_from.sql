@@ -0,0 +1,5 @@
+-- first height recorded in `blocks`. Databases that were loading before blocks were
+-- tracked have no rows for their earlier history, so `verify` starts from here.
+ALTER TABLE follower_info ADD COLUMN blocks_from BIGINT;
+
+UPDATE follower_info SET blocks_from = COALESCE((SELECT min(height) FROM blocks), height + 1);
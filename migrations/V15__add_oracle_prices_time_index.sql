@@ -0,0 +1,2 @@
+-- CSV export looks up the reports in a time window before each reward
+CREATE INDEX oracle_prices_time_idx on oracle_prices(time);
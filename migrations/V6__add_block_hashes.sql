@@ -0,0 +1,8 @@
+ALTER TABLE follower_info ADD COLUMN hash TEXT;
+
+CREATE TABLE block_hashes (
+       height BIGINT NOT NULL,
+       hash TEXT NOT NULL,
+
+       PRIMARY KEY (height)
+);
@@ -0,0 +1,10 @@
+ALTER TABLE block_hashes RENAME TO blocks;
+ALTER INDEX block_hashes_pkey RENAME TO blocks_pkey;
+
+-- blocks loaded before this migration only have a height and hash
+ALTER TABLE blocks
+       ADD COLUMN prev_hash TEXT,
+       ADD COLUMN time BIGINT,
+       ADD COLUMN transaction_count INTEGER;
+
+CREATE INDEX blocks_time_idx on blocks(time);
@@ -0,0 +1,2 @@
+-- number of rewards loaded for the block, used to detect partially-loaded blocks
+ALTER TABLE blocks ADD COLUMN reward_count INTEGER;
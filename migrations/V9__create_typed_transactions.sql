@@ -0,0 +1,104 @@
+CREATE TABLE payments (
+       block BIGINT NOT NULL,
+       hash TEXT NOT NULL,
+       type transaction_type NOT NULL,
+       payer TEXT NOT NULL,
+       payee TEXT NOT NULL,
+       amount BIGINT NOT NULL,
+       fee BIGINT NOT NULL,
+       nonce BIGINT NOT NULL,
+       memo TEXT
+);
+
+CREATE INDEX payments_block_idx on payments(block);
+CREATE INDEX payments_hash_idx on payments(hash);
+CREATE INDEX payments_payer_idx on payments(payer);
+CREATE INDEX payments_payee_idx on payments(payee);
+
+CREATE TABLE add_gateways (
+       block BIGINT NOT NULL,
+       hash TEXT NOT NULL,
+       owner TEXT NOT NULL,
+       gateway TEXT NOT NULL,
+       payer TEXT,
+       fee BIGINT NOT NULL,
+       staking_fee BIGINT NOT NULL,
+
+       PRIMARY KEY (hash)
+);
+
+CREATE INDEX add_gateways_block_idx on add_gateways(block);
+CREATE INDEX add_gateways_owner_idx on add_gateways(owner);
+CREATE INDEX add_gateways_gateway_idx on add_gateways(gateway);
+
+CREATE TABLE assert_locations (
+       block BIGINT NOT NULL,
+       hash TEXT NOT NULL,
+       type transaction_type NOT NULL,
+       owner TEXT NOT NULL,
+       gateway TEXT NOT NULL,
+       payer TEXT,
+       location TEXT NOT NULL,
+       nonce BIGINT NOT NULL,
+       fee BIGINT NOT NULL,
+       staking_fee BIGINT NOT NULL,
+       gain INTEGER,
+       elevation INTEGER,
+
+       PRIMARY KEY (hash)
+);
+
+CREATE INDEX assert_locations_block_idx on assert_locations(block);
+CREATE INDEX assert_locations_gateway_idx on assert_locations(gateway);
+CREATE INDEX assert_locations_location_idx on assert_locations(location);
+
+CREATE TABLE transfer_hotspots (
+       block BIGINT NOT NULL,
+       hash TEXT NOT NULL,
+       type transaction_type NOT NULL,
+       gateway TEXT NOT NULL,
+       seller TEXT NOT NULL,
+       buyer TEXT NOT NULL,
+       amount_to_seller BIGINT,
+       fee BIGINT NOT NULL,
+
+       PRIMARY KEY (hash)
+);
+
+CREATE INDEX transfer_hotspots_block_idx on transfer_hotspots(block);
+CREATE INDEX transfer_hotspots_gateway_idx on transfer_hotspots(gateway);
+CREATE INDEX transfer_hotspots_seller_idx on transfer_hotspots(seller);
+CREATE INDEX transfer_hotspots_buyer_idx on transfer_hotspots(buyer);
+
+-- one row per summary in the closed state channel
+CREATE TABLE state_channel_closes (
+       block BIGINT NOT NULL,
+       hash TEXT NOT NULL,
+       closer TEXT NOT NULL,
+       state_channel_id TEXT NOT NULL,
+       owner TEXT NOT NULL,
+       gateway TEXT NOT NULL,
+       num_dcs BIGINT NOT NULL,
+       num_packets BIGINT NOT NULL,
+       fee BIGINT NOT NULL
+);
+
+CREATE INDEX state_channel_closes_block_idx on state_channel_closes(block);
+CREATE INDEX state_channel_closes_hash_idx on state_channel_closes(hash);
+CREATE INDEX state_channel_closes_gateway_idx on state_channel_closes(gateway);
+
+-- one row per challengee in the receipt's path
+CREATE TABLE poc_receipts (
+       block BIGINT NOT NULL,
+       hash TEXT NOT NULL,
+       challenger TEXT NOT NULL,
+       challengee TEXT NOT NULL,
+       path_position INTEGER NOT NULL,
+       witnesses INTEGER NOT NULL,
+       fee BIGINT NOT NULL
+);
+
+CREATE INDEX poc_receipts_block_idx on poc_receipts(block);
+CREATE INDEX poc_receipts_hash_idx on poc_receipts(hash);
+CREATE INDEX poc_receipts_challenger_idx on poc_receipts(challenger);
+CREATE INDEX poc_receipts_challengee_idx on poc_receipts(challengee);
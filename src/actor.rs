@@ -0,0 +1,135 @@
+use crate::*;
+use tokio_postgres::{ Transaction as PgTransaction };
+use helium_jsonrpc::Transaction;
+use std::convert::TryFrom;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Role {
+  Payer,
+  Payee,
+  Owner,
+  Gateway,
+  Challenger,
+  Challengee,
+  Witness,
+  ScOpener,
+  ScCloser,
+  PacketReceiver,
+}
+
+impl Role {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      Role::Payer => "payer",
+      Role::Payee => "payee",
+      Role::Owner => "owner",
+      Role::Gateway => "gateway",
+      Role::Challenger => "challenger",
+      Role::Challengee => "challengee",
+      Role::Witness => "witness",
+      Role::ScOpener => "sc_opener",
+      Role::ScCloser => "sc_closer",
+      Role::PacketReceiver => "packet_receiver",
+    }
+  }
+}
+
+// Transaction types `actors` knows how to extract actors from.
+pub const ACTOR_TRANSACTION_TYPES: &[&str] = &[
+  "payment_v1",
+  "payment_v2",
+  "add_gateway_v1",
+  "assert_location_v1",
+  "assert_location_v2",
+  "transfer_hotspot_v1",
+  "transfer_hotspot_v2",
+  "state_channel_close_v1",
+  "poc_receipts_v1",
+];
+
+/// Every account or gateway taking part in a transaction, with the role it plays.
+/// Rewards are not included since they are already loaded into `rewards`.
+pub fn actors(transaction: &Transaction) -> Vec<(&str, Role)> {
+  let mut actors: Vec<(&str, Role)> = vec!();
+  match transaction {
+    Transaction::PaymentV1(p) => {
+      actors.push((&p.payer, Role::Payer));
+      actors.push((&p.payee, Role::Payee));
+    },
+    Transaction::PaymentV2(p) => {
+      actors.push((&p.payer, Role::Payer));
+      for payment in &p.payments {
+        actors.push((&payment.payee, Role::Payee));
+      }
+    },
+    Transaction::AddGatewayV1(g) => {
+      actors.push((&g.owner, Role::Owner));
+      actors.push((&g.gateway, Role::Gateway));
+      if let Some(payer) = &g.payer {
+        actors.push((payer, Role::Payer));
+      }
+    },
+    Transaction::AssertLocationV1(a) => {
+      actors.push((&a.owner, Role::Owner));
+      actors.push((&a.gateway, Role::Gateway));
+      if let Some(payer) = &a.payer {
+        actors.push((payer, Role::Payer));
+      }
+    },
+    Transaction::AssertLocationV2(a) => {
+      actors.push((&a.owner, Role::Owner));
+      actors.push((&a.gateway, Role::Gateway));
+      if let Some(payer) = &a.payer {
+        actors.push((payer, Role::Payer));
+      }
+    },
+    Transaction::TransferHotspotV1(t) => {
+      actors.push((&t.gateway, Role::Gateway));
+      actors.push((&t.seller, Role::Payee));
+      actors.push((&t.buyer, Role::Payer));
+      actors.push((&t.buyer, Role::Owner));
+    },
+    Transaction::TransferHotspotV2(t) => {
+      actors.push((&t.gateway, Role::Gateway));
+      actors.push((&t.owner, Role::Payer));
+      actors.push((&t.new_owner, Role::Owner));
+    },
+    Transaction::StateChannelCloseV1(sc) => {
+      actors.push((&sc.closer, Role::ScCloser));
+      actors.push((&sc.state_channel.owner, Role::ScOpener));
+      for summary in &sc.state_channel.summaries {
+        actors.push((&summary.client, Role::PacketReceiver));
+      }
+    },
+    Transaction::PocReceiptsV1(poc) => {
+      actors.push((&poc.challenger, Role::Challenger));
+      for element in &poc.path {
+        actors.push((&element.challengee, Role::Challengee));
+        for witness in &element.witnesses {
+          actors.push((&witness.gateway, Role::Witness));
+        }
+      }
+    },
+    _ => (),
+  }
+  actors
+}
+
+/// Insert the actors of every transaction in a block in a single statement.
+/// Each entry is (transaction hash, actor, role).
+pub async fn add_actors<'a>(pgtran: &'a PgTransaction<'a>, block: u64, actors: &[(&str, &str, Role)]) -> Result<u64> {
+  if actors.is_empty() {
+    return Ok(0);
+  }
+
+  let hashes: Vec<&str> = actors.iter().map(|a| a.0).collect();
+  let addresses: Vec<&str> = actors.iter().map(|a| a.1).collect();
+  let roles: Vec<&str> = actors.iter().map(|a| a.2.as_str()).collect();
+  let blocks: Vec<i64> = vec![i64::try_from(block).unwrap(); actors.len()];
+
+  pgtran.execute("INSERT INTO transaction_actors (actor, actor_role, transaction_hash, block)
+    SELECT * FROM unnest($1::text[], $2::text[]::transaction_actor_role[], $3::text[], $4::bigint[])
+    ON CONFLICT DO NOTHING", &[&addresses, &roles, &hashes, &blocks])
+    .await
+    .map_err(|e| error::Error::PgError(e))
+}
@@ -0,0 +1,206 @@
+use crate::*;
+use crate::block_processor;
+use slog::{error, info, Logger};
+use helium_api::models::transactions::Reward;
+use helium_jsonrpc::Transaction;
+use deadpool_postgres::Pool;
+use futures::stream::{self, StreamExt};
+use std::{convert::TryFrom, time::Duration};
+use tokio::time;
+
+// How often to look for filters that still need a backfill.
+const BACKFILL_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Loads historical rewards for filters added after the follower started. Each filter
+/// covers `first_block` up to the height it was picked up at (`filters.added_height`),
+/// and progress is recorded in `filters.backfill_height` so it resumes after restarts.
+pub struct Backfill {
+  client: rpc::NodeClient,
+  pool: Pool,
+  prefetch: usize,
+  shutdown: triggered::Listener,
+  logger: Logger,
+}
+
+impl Backfill {
+  pub fn new(settings: &Settings, pool: Pool, logger: &Logger, shutdown: triggered::Listener) -> Self {
+    Self {
+      client: rpc::NodeClient::new(settings),
+      pool,
+      prefetch: settings.prefetch.max(1),
+      shutdown,
+      logger: logger.new(slog::o!("module" => "Backfill")),
+    }
+  }
+
+  pub async fn run(mut self) {
+    let mut interval = time::interval(BACKFILL_INTERVAL);
+    loop {
+      tokio::select! {
+        _ = self.shutdown.clone() => {
+          info!(self.logger, "shutting down backfill");
+          return
+        },
+        _ = interval.tick() => {
+          if let Err(e) = self.backfill_pending().await {
+            error!(self.logger, "error backfilling filters: {}", e);
+          }
+        }
+      }
+    }
+  }
+
+  async fn backfill_pending(&mut self) -> Result<()> {
+    let mut pgclient = self.pool.get().await?;
+    let rows = pgclient.query("SELECT first_block FROM follower_info", &[]).await?;
+    let first_block = match rows.first() {
+      Some(r) => u64::try_from(r.get::<_, i64>(0)).unwrap(),
+      None => return Ok(()),
+    };
+
+    let filters = filter::get_states(&pgclient).await?;
+    let pending: Vec<&filter::FilterState> = filters
+      .iter()
+      .filter(|f| match f.added_height {
+        Some(added) => f.backfill_height.map_or(first_block, |b| b + 1) <= added,
+        None => false,
+      })
+      .collect();
+
+    let from = match pending.iter().map(|f| f.backfill_height.map_or(first_block, |b| b + 1)).min() {
+      Some(h) => h.max(first_block),
+      None => return Ok(()),
+    };
+    let to = pending.iter().filter_map(|f| f.added_height).max().unwrap_or(from);
+    info!(self.logger, "backfilling {} filters from {} to {}", pending.len(), from, to);
+
+    let client = &self.client;
+    let mut fetched = stream::iter(from..=to)
+      .map(|height| block_processor::fetch(EtlMode::Rewards, client, height))
+      .buffered(self.prefetch);
+
+    while let Some(block) = fetched.next().await {
+      if self.shutdown.is_triggered() {
+        return Ok(());
+      }
+      let block = block?;
+      let height = block.height();
+
+      let mut rewards: Vec<&Reward> = vec!();
+      for txn in &block.transactions {
+        if let Transaction::RewardsV2(rewards_v2) = &txn.transaction {
+          rewards.extend(rewards_v2.rewards.iter().filter(|r| needs_backfill(&filters, height, r)));
+        }
+      }
+
+      // filters this block is being backfilled for
+      let values: Vec<&str> = filters
+        .iter()
+        .filter(|f| f.added_height.map_or(false, |added| height <= added) && !f.covers(height))
+        .map(|f| f.value.as_str())
+        .collect();
+
+      let pgtran = pgclient.build_transaction().start().await?;
+      // a rollback may have moved these filters below this height since they were read,
+      // leaving it to the follower. Wait for it to commit and start over if so.
+      let still_pending = pgtran.query("SELECT value FROM filters WHERE value = ANY($1) AND added_height >= $2 FOR UPDATE",
+        &[&values, &i64::try_from(height).unwrap()]).await?;
+      if still_pending.len() < values.len() {
+        info!(self.logger, "filters rolled back below block {}, restarting backfill", height);
+        return Ok(());
+      }
+      let count = reward::add_rewards(&pgtran, height, block.block.time, block.block.hash.to_string(), &rewards).await?;
+      if count > 0 {
+        info!(self.logger, "backfilled {} rewards in block {}", count, height);
+        block::add_reward_count(&pgtran, height, count).await?;
+      }
+      pgtran.execute("UPDATE filters SET backfill_height = $1 WHERE value = ANY($2)",
+        &[&i64::try_from(height).unwrap(), &values]).await?;
+      pgtran.commit().await?;
+    }
+
+    Ok(())
+  }
+}
+
+// A reward needs to be backfilled when it matches a filter whose backfill covers this
+// height and no matching filter has already loaded it.
+fn needs_backfill(filters: &[filter::FilterState], height: u64, reward: &Reward) -> bool {
+  let account = reward.account.as_deref();
+  let gateway = reward.gateway.as_deref();
+  let mut matched = false;
+  for f in filters.iter().filter(|f| f.matches(account, gateway)) {
+    if f.covers(height) {
+      return false;
+    }
+    if f.added_height.is_some() {
+      matched = true;
+    }
+  }
+  matched
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::collections::HashSet;
+
+  fn state(r#type: &str, value: &str, added_height: Option<u64>, backfill_height: Option<u64>) -> filter::FilterState {
+    filter::FilterState {
+      r#type: r#type.to_string(),
+      value: value.to_string(),
+      added_height,
+      backfill_height,
+      owned_gateways: HashSet::new(),
+    }
+  }
+
+  fn reward(account: &str, gateway: &str) -> Reward {
+    serde_json::from_value(serde_json::json!({
+      "account": account, "gateway": gateway, "amount": 1000, "type": "poc_witnesses"
+    })).unwrap()
+  }
+
+  #[test]
+  fn unmatched_reward_is_skipped() {
+    let filters = vec![state("account", "account1", Some(10), None)];
+    assert!(!needs_backfill(&filters, 5, &reward("account2", "gateway2")));
+  }
+
+  #[test]
+  fn matched_reward_below_added_height_is_backfilled() {
+    let filters = vec![state("account", "account1", Some(10), None)];
+    assert!(needs_backfill(&filters, 5, &reward("account1", "gateway1")));
+    assert!(needs_backfill(&filters, 10, &reward("account1", "gateway1")));
+  }
+
+  #[test]
+  fn reward_the_follower_loaded_is_skipped() {
+    let filters = vec![state("account", "account1", Some(10), None)];
+    assert!(!needs_backfill(&filters, 11, &reward("account1", "gateway1")));
+  }
+
+  #[test]
+  fn reward_already_backfilled_is_skipped() {
+    let filters = vec![state("account", "account1", Some(10), Some(6))];
+    assert!(!needs_backfill(&filters, 6, &reward("account1", "gateway1")));
+    assert!(needs_backfill(&filters, 7, &reward("account1", "gateway1")));
+  }
+
+  #[test]
+  fn reward_loaded_for_another_filter_is_skipped() {
+    // the gateway filter was there all along, so the follower already loaded this reward
+    let filters = vec![
+      state("account", "account1", Some(10), None),
+      state("gateway", "gateway1", Some(0), Some(0)),
+    ];
+    assert!(!needs_backfill(&filters, 5, &reward("account1", "gateway1")));
+    assert!(needs_backfill(&filters, 5, &reward("account1", "gateway2")));
+  }
+
+  #[test]
+  fn unstamped_filter_is_not_backfilled() {
+    let filters = vec![state("account", "account1", None, None)];
+    assert!(!needs_backfill(&filters, 5, &reward("account1", "gateway1")));
+  }
+}
@@ -0,0 +1,39 @@
+use crate::*;
+use helium_jsonrpc::blocks::BlockRaw;
+use tokio_postgres::{ Client as PgClient, Statement, Transaction as PgTransaction };
+use std::convert::TryFrom;
+
+pub async fn prepare<'a>(pgtran: &'a PgTransaction<'a>) -> Result<Statement>{
+  let stmt = pgtran.prepare("INSERT INTO blocks (height, hash, prev_hash, time, transaction_count, reward_count)
+    VALUES ($1, $2, $3, $4, $5, $6)").await;
+  match stmt {
+    Ok(s) => Ok(s),
+    Err(e) => Err(error::Error::PgError(e)),
+  }
+}
+
+pub async fn add_block<'a>(pgtran: &'a PgTransaction<'a>, block: &BlockRaw, reward_count: u64) -> Result<u64> {
+  let stmt = prepare(&pgtran).await?;
+  pgtran.execute(&stmt, &[&i64::try_from(block.height).unwrap(),
+    &block.hash,
+    &block.prev_hash,
+    &i64::try_from(block.time).unwrap(),
+    &i32::try_from(block.transactions.len()).unwrap(),
+    &i32::try_from(reward_count).unwrap()])
+    .await
+    .map_err(|e| error::Error::PgError(e))
+}
+
+pub async fn get_hash(pgclient: &PgClient, height: u64) -> Result<Option<String>> {
+  let rows = pgclient.query("SELECT hash FROM blocks WHERE height = $1",
+    &[&i64::try_from(height).unwrap()]).await?;
+  Ok(rows.first().map(|r| r.get(0)))
+}
+
+/// Account for rewards loaded for a block after it was first committed.
+pub async fn add_reward_count<'a>(pgtran: &'a PgTransaction<'a>, height: u64, reward_count: u64) -> Result<u64> {
+  pgtran.execute("UPDATE blocks SET reward_count = reward_count + $2 WHERE height = $1",
+    &[&i64::try_from(height).unwrap(), &i32::try_from(reward_count).unwrap()])
+    .await
+    .map_err(|e| error::Error::PgError(e))
+}
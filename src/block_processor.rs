@@ -1,155 +1,186 @@
 use crate::*;
-use slog::{error, info, Logger};
-use helium_jsonrpc::{ Client, blocks, blocks::BlockRaw, transactions, Transaction };
+use slog::{info, Logger};
+use helium_jsonrpc::{ blocks::BlockRaw, Transaction };
+use helium_api::models::transactions::Reward;
 use tokio_postgres::{ Transaction as PgTransaction };
+use futures::stream::{self, StreamExt, TryStreamExt};
 use std::convert::TryFrom;
 
+// Number of transactions fetched concurrently from the node for a single block.
+const TRANSACTION_CONCURRENCY: usize = 16;
+
+/// A block along with every transaction needed to load it in the current mode.
+/// Fetching is done ahead of time so that many blocks can be pulled from the node
+/// concurrently while still being committed in order.
+pub struct FetchedBlock {
+  pub block: BlockRaw,
+  pub transactions: Vec<FetchedTransaction>,
+}
+
+pub struct FetchedTransaction {
+  pub hash: String,
+  pub r#type: String,
+  pub transaction: Transaction,
+}
+
+impl FetchedBlock {
+  pub fn height(&self) -> u64 {
+    self.block.height
+  }
+}
+
+/// Fetch a block and the transactions the given mode loads from the node.
+pub async fn fetch(mode: EtlMode, client: &rpc::NodeClient, height: u64) -> Result<FetchedBlock> {
+  let block = match client.get_block(height).await {
+    Ok(b) => b,
+    Err(e) => return Err(error::Error::Custom(format!("couldn't get block {}: {:?}", height, e))),
+  };
+
+  let wanted: Vec<(String, String)> = block.transactions
+    .iter()
+    .filter(|txn| match mode {
+      EtlMode::Full => true,
+      EtlMode::Filters => txn.r#type == "rewards_v2"
+        || actor::ACTOR_TRANSACTION_TYPES.contains(&txn.r#type.as_str())
+        || oracle::ORACLE_TRANSACTION_TYPES.contains(&txn.r#type.as_str()),
+      EtlMode::Rewards => txn.r#type == "rewards_v2"
+        || ownership::OWNERSHIP_TRANSACTION_TYPES.contains(&txn.r#type.as_str())
+        || oracle::ORACLE_TRANSACTION_TYPES.contains(&txn.r#type.as_str()),
+    })
+    .map(|txn| (txn.hash.to_string(), txn.r#type.to_string()))
+    .collect();
+
+  let transactions = stream::iter(wanted)
+    .map(|(hash, r#type)| async move {
+      match client.get_transaction(&hash).await {
+        Ok(transaction) => Ok(FetchedTransaction { hash, r#type, transaction }),
+        Err(e) => Err(error::Error::Custom(format!("Error getting transaction: [{}] {} {:?}", r#type, hash, e))),
+      }
+    })
+    .buffered(TRANSACTION_CONCURRENCY)
+    .try_collect()
+    .await?;
+
+  Ok(FetchedBlock { block, transactions })
+}
+
 pub struct BlockProcessor<'a> {
     mode: EtlMode,
-    height: u64,
-    client: &'a Client,
-    pgtran: PgTransaction<'a>,
+    block: FetchedBlock,
     logger: &'a Logger,
     filters: &'a filter::Filters,
 }
 
 impl<'a> BlockProcessor<'a> {
-    pub fn new(mode: EtlMode, height: u64, client: &'a Client, pgtran: PgTransaction<'a>, logger: &'a Logger, filters: &'a filter::Filters) -> Self {
+    pub fn new(mode: EtlMode, block: FetchedBlock, logger: &'a Logger, filters: &'a filter::Filters) -> Self {
         BlockProcessor{
             mode,
-            height,
-            client,
-            pgtran,
+            block,
             logger,
             filters
         }
     }
 
-    pub async fn process(mut self) -> Result<()> {
-        match blocks::get_raw(&self.client, &self.height).await {
-            Ok(b) => self.load_block(b).await?,
-            Err(e) => {
-                error!(self.logger, "Couldn't get block {}: {:?}", self.height, e);
-                return Err(error::Error::Custom(format!("couldn't get block {}: {:?}", self.height, e)))
-            },
-        }
+    /// Write the block to the sink, advancing the follower's position.
+    pub async fn process(self, sink: &mut dyn sink::Sink) -> Result<()> {
+        let selected = self.select();
+        let reward_count = u64::try_from(selected.rewards.len()).unwrap();
+        sink.write_block(&selected).await?;
 
-        info!(self.logger, "got block {}", self.height);
+        info!(self.logger, "got block {}", self.block.height());
 
-        match self.update_follower_info_height().await {
-            Ok(_) => {},
-            Err(e) => return Err(e),
+        metrics::BLOCKS.inc();
+        metrics::HEIGHT.set(i64::try_from(self.block.height()).unwrap());
+        metrics::REWARDS.inc_by(reward_count);
+        for t in &self.block.block.transactions {
+          metrics::TRANSACTIONS.with_label_values(&[t.r#type.as_str()]).inc();
         }
+        Ok(())
+    }
+
+    /// Load a block that may already be in Postgres, replacing whatever was stored
+    /// for it. follower_info is left untouched.
+    pub async fn reload(self, pgtran: PgTransaction<'_>) -> Result<()> {
+        reorg::delete_block(&pgtran, self.block.height()).await?;
+        sink::postgres::load(&pgtran, &self.select()).await?;
+
+        info!(self.logger, "reloaded block {}", self.block.height());
 
-        match self.pgtran.commit().await {
+        match pgtran.commit().await {
             Ok(_) => Ok(()),
             Err(e) => Err(error::Error::custom(e.to_string())),
         }
     }
 
-    async fn load_block(&self, block: BlockRaw) -> Result<()> {
+    // Pick out what the current mode and filters load from the block.
+    fn select(&self) -> sink::Block<'_> {
+        let block = &self.block.block;
+        let mut selected = sink::Block {
+            block,
+            rewards: vec!(),
+            transactions: vec!(),
+            actors: vec!(),
+            owners: vec!(),
+            prices: vec!(),
+        };
         match self.mode {
             EtlMode::Full => info!(self.logger, "Loading txns in block {}", block.height),
             _ => (),
           }
-          for txn in &block.transactions {
-            match txn.r#type.as_str() {
-              "rewards_v2" => {
-                let rewards = match transactions::get(&self.client, &txn.hash).await {
-                  Ok(t) => match t {
-                    Transaction::RewardsV2(rewards) => rewards.rewards,
-                    _ => {
-                      return Err(error::Error::Custom(format!("Error getting rewards txn: '{}'", txn.hash)))
-                    }
-                  },
-                  Err(e) => {
-                    return Err(error::Error::Custom(format!("Error getting rewards txn: '{}' {:?}", txn.hash, e)))
-                  }
-                };
+          for txn in &self.block.transactions {
+            match &txn.transaction {
+              Transaction::RewardsV2(rewards) => {
+                let rewards = &rewards.rewards;
                 info!(self.logger, "rewards in block {} with {}", block.height.to_string(), rewards.len());
-                'rloop: for r in rewards {
-                  match self.mode {
-                    EtlMode::Rewards | EtlMode::Full => {
-                      match reward::add_reward(&self.pgtran, block.height, block.time, block.hash.to_string(), &r).await {
-                        Ok(_) => (),
-                        Err(e) => {
-                          return Err(Error::Custom(format!("Error adding reward {:?}", e)));
-                        },
-                      }
-                    },
-                    EtlMode::Filters => {
-                      match r.account {
-                        Some(ref a) => {
-                          for filter_account in &self.filters.accounts {
-                            match &a {
-                              f if f == &filter_account => {
-                                info!(self.logger, "loading reward for account: {} -> {}", filter_account, r.r#type);
-                                match reward::add_reward(&self.pgtran, block.height, block.time, block.hash.to_string(), &r).await {
-                                  Ok(_) => (),
-                                  Err(e) => {
-                                    return Err(Error::Custom(format!("Error adding reward {:?}", e)));
-                                  },
-                                }                             
-                                continue 'rloop;
-                              },
-                              _ => (),
-                            }
-                          }
-                        },
-                        None => (),
-                      }
-                      match r.gateway {
-                        Some(ref g) => {
-                          for filter_gateway in &self.filters.gateways {
-                            match &g {
-                              f if f == &filter_gateway => {
-                                info!(self.logger, "loading reward for gateway: {} -> {}", filter_gateway, r.r#type);
-                                match reward::add_reward(&self.pgtran, block.height, block.time, block.hash.to_string(), &r).await {
-                                  Ok(_) => (),
-                                  Err(e) => {
-                                    return Err(Error::Custom(format!("Error adding reward {:?}", e)));
-                                  },
-                                }                                                           
-                                continue 'rloop;
-                              },
-                              _ => (),
-                            }
-                          }
-                        },
-                        None => (),                     
-                      }
-                    },
-                  }
-                }
+                match self.mode {
+                  EtlMode::Rewards | EtlMode::Full => selected.rewards.extend(rewards.iter()),
+                  EtlMode::Filters => selected.rewards.extend(rewards.iter().filter(|r| self.filter_reward(r))),
+                };
               },
               _ => (),
             }
-            match self.mode {
-              EtlMode::Full => {
-      
-                let transaction = match transactions::get(&self.client, &txn.hash).await {
-                  Ok(t) => t,
-                  Err(e) => {
-                    return Err(error::Error::Custom(format!("Error getting transaction: [{}] {} {:?}",  txn.r#type, txn.hash, e)))
-                  }
-                };
-                match transaction::add_transaction(&self.pgtran, block.height, txn.hash.to_string(), txn.r#type.as_str(), transaction).await {
-                  Ok(_) => (),
-                  Err(e) => {
-                    return Err(Error::Custom(format!("Error adding transaction: {}. {:?}", txn.hash, e)));
-                  },
-                }
-              },
-              _ => (),              
+            if let Some((gateway, owner)) = ownership::owner_change(&txn.transaction) {
+              selected.owners.push((txn.hash.as_str(), gateway, owner));
+            }
+            if let Some((public_key, price)) = oracle::price_report(&txn.transaction) {
+              selected.prices.push((txn.hash.as_str(), public_key, price));
+            }
+            let store = match self.mode {
+              EtlMode::Full => true,
+              EtlMode::Filters => self.filter_transaction(txn),
+              EtlMode::Rewards => false,
+            };
+            if store {
+              selected.transactions.push(txn);
+              selected.actors.extend(actor::actors(&txn.transaction).into_iter().map(|(a, role)| (txn.hash.as_str(), a, role)));
             }
           }
-          Ok(()) 
+          selected
     }
 
-    async fn update_follower_info_height(&self) -> Result<Vec<tokio_postgres::Row>>{
-        let stmt = self.pgtran.prepare("UPDATE follower_info SET height = $1").await.unwrap();
-        self.pgtran.query(&stmt, &[&i64::try_from(self.height).unwrap()])
-          .await
-          .map_err(|e| error::Error::PgError(e))
-      }
-}
\ No newline at end of file
+    fn filter_reward(&self, r: &Reward) -> bool {
+        if let Some(a) = &r.account {
+          if self.filters.has_account(a) {
+            info!(self.logger, "loading reward for account: {} -> {}", a, r.r#type);
+            return true;
+          }
+        }
+        if let Some(g) = &r.gateway {
+          if self.filters.has_gateway(g) {
+            info!(self.logger, "loading reward for gateway: {} -> {}", g, r.r#type);
+            return true;
+          }
+        }
+        false
+    }
+
+    fn filter_transaction(&self, txn: &FetchedTransaction) -> bool {
+        for (a, role) in actor::actors(&txn.transaction) {
+          if self.filters.has_address(a) {
+            info!(self.logger, "loading {} {} for {}: {}", txn.r#type, txn.hash, role.as_str(), a);
+            return true;
+          }
+        }
+        false
+    }
+}
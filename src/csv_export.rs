@@ -0,0 +1,148 @@
+use crate::*;
+use crate::export::Range;
+use chrono::{DateTime, Duration, NaiveDateTime, SecondsFormat, Utc};
+use slog::{info, Logger};
+use tokio_postgres::Client as PgClient;
+use std::{convert::TryFrom, fs, path::Path};
+
+// bones per HNT, and oracle prices are in 1/100_000_000 of a USD
+const BONES_PER_HNT: i128 = 100_000_000;
+// The window of oracle reports the chain takes its price from, in seconds before the
+// block: mainnet's price_oracle_price_scan_max and price_oracle_price_scan_delay.
+const PRICE_SCAN_MAX_SECS: i64 = 90_000;
+const PRICE_SCAN_DELAY_SECS: i64 = 3_600;
+const BASE58_ALPHABET: &str = "123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// Whose rewards to export.
+#[derive(Debug, Clone)]
+pub enum Target {
+  Account(String),
+  Gateway(String),
+}
+
+impl Target {
+  fn column(&self) -> &'static str {
+    match self {
+      Target::Account(_) => "account",
+      Target::Gateway(_) => "gateway",
+    }
+  }
+
+  fn address(&self) -> &str {
+    match self {
+      Target::Account(a) | Target::Gateway(a) => a,
+    }
+  }
+}
+
+/// Whether `address` only uses base58 characters, so it is safe to put in a file name.
+pub fn is_address(address: &str) -> bool {
+  !address.is_empty() && address.chars().all(|c| BASE58_ALPHABET.contains(c))
+}
+
+/// Accounts and gateways in the filters table. Owner filters are exported as accounts,
+/// since that is where their hotspots' rewards are paid.
+pub async fn filter_targets(pgclient: &PgClient) -> Result<Vec<Target>> {
+  let filters = filter::get(pgclient).await?;
+  let mut targets: Vec<Target> = filters.accounts.union(&filters.owners).cloned().map(Target::Account).collect();
+  targets.extend(filters.gateways.into_iter().map(Target::Gateway));
+  Ok(targets)
+}
+
+/// Write the rewards of `target` to `dir/<account|gateway>-<address>.csv`, one row per
+/// reward with its block, UTC timestamp, type, amount in HNT and, where oracle price
+/// reports before the block are loaded, the oracle price and USD value. Like the chain,
+/// the price is the median of each oracle's latest report within the price scan window.
+/// Returns the number of rewards written.
+pub async fn export(pgclient: &PgClient, logger: &Logger, dir: &Path, target: &Target, range: &Range) -> Result<u64> {
+  if !is_address(target.address()) {
+    return Err(error::Error::Custom(format!("{} {} is not a base58 address", target.column(), target.address())));
+  }
+  let query = format!("SELECT r.block, r.time, r.transaction_hash, r.account, r.gateway, r.type, r.amount,
+      (SELECT floor(percentile_cont(0.5) WITHIN GROUP (ORDER BY latest.price))::bigint
+        FROM (SELECT DISTINCT ON (p.public_key) p.price FROM oracle_prices p
+          WHERE p.time BETWEEN r.time - $6 AND r.time - $7
+          ORDER BY p.public_key, p.block DESC, p.transaction_hash) latest) AS price
+    FROM rewards r
+    WHERE r.{} = $1
+      AND ($2::bigint IS NULL OR r.block >= $2) AND ($3::bigint IS NULL OR r.block <= $3)
+      AND ($4::bigint IS NULL OR r.time >= $4) AND ($5::bigint IS NULL OR r.time < $5)
+    ORDER BY r.block", target.column());
+  let rows = pgclient.query(query.as_str(), &[
+    &target.address(),
+    &range.from_height.map(|h| i64::try_from(h).unwrap()),
+    &range.to_height.map(|h| i64::try_from(h).unwrap()),
+    &range.from_date.map(|d| d.and_hms(0, 0, 0).timestamp()),
+    &range.to_date.map(|d| (d + Duration::days(1)).and_hms(0, 0, 0).timestamp()),
+    &PRICE_SCAN_MAX_SECS,
+    &PRICE_SCAN_DELAY_SECS,
+  ]).await?;
+
+  fs::create_dir_all(dir)?;
+  let path = dir.join(format!("{}-{}.csv", target.column(), target.address()));
+  let mut writer = csv::Writer::from_path(&path)?;
+  writer.write_record(&["block", "timestamp", "transaction_hash", "account", "gateway", "type",
+    "amount_hnt", "oracle_price_usd", "value_usd"])?;
+  for row in &rows {
+    let time: i64 = row.get("time");
+    let amount: i64 = row.get("amount");
+    let price: Option<i64> = row.get("price");
+    writer.write_record(&[
+      row.get::<_, i64>("block").to_string(),
+      timestamp(time),
+      row.get("transaction_hash"),
+      row.get("account"),
+      row.get("gateway"),
+      row.get("type"),
+      decimal(i128::from(amount)),
+      price.map_or(String::new(), |p| decimal(i128::from(p))),
+      price.map_or(String::new(), |p| decimal(i128::from(amount) * i128::from(p) / BONES_PER_HNT)),
+    ])?;
+  }
+  writer.flush()?;
+
+  info!(logger, "wrote {} rewards to {}", rows.len(), path.display());
+  Ok(u64::try_from(rows.len()).unwrap())
+}
+
+fn timestamp(time: i64) -> String {
+  DateTime::<Utc>::from_utc(NaiveDateTime::from_timestamp(time, 0), Utc).to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+// Formats an amount in 1/100_000_000 units exactly, e.g. 150000000 as "1.50000000".
+fn decimal(units: i128) -> String {
+  let sign = if units < 0 { "-" } else { "" };
+  let units = units.abs();
+  format!("{}{}.{:08}", sign, units / BONES_PER_HNT, units % BONES_PER_HNT)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn decimal_keeps_every_digit() {
+    assert_eq!(decimal(0), "0.00000000");
+    assert_eq!(decimal(1), "0.00000001");
+    assert_eq!(decimal(150_000_000), "1.50000000");
+    assert_eq!(decimal(12_345_678_901), "123.45678901");
+    assert_eq!(decimal(-150_000_000), "-1.50000000");
+    assert_eq!(decimal(-1), "-0.00000001");
+  }
+
+  #[test]
+  fn base58_addresses_are_accepted() {
+    assert!(is_address("13oNZxczcP2urLzQTGQVFpezg4C3EADqjcTmDDH5yrpAzi389HL"));
+    assert!(is_address("112qB3YaH5bZkCnKA5uRH7tBtGNv2Y5B4smv1jsmvGUzgKT71QpE"));
+  }
+
+  #[test]
+  fn anything_else_is_rejected() {
+    assert!(!is_address(""));
+    assert!(!is_address("../etc/passwd"));
+    assert!(!is_address("13oNZ/xczcP2"));
+    assert!(!is_address("13oNZ xczcP2"));
+    // 0, O, I and l aren't in the base58 alphabet
+    assert!(!is_address("0OIl"));
+  }
+}
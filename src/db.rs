@@ -0,0 +1,63 @@
+use crate::*;
+use deadpool_postgres::{Manager, ManagerConfig, Pool, RecyclingMethod};
+use native_tls::{Certificate, Identity, TlsConnector};
+use postgres_native_tls::MakeTlsConnector;
+use tokio_postgres::{config::SslMode, Client as PgClient, Config};
+use std::fs;
+
+/// A pool of Postgres connections. Connections that were closed, e.g. by a database
+/// restart, are discarded when they are next handed out and replaced with new ones.
+pub fn pool(settings: &Settings) -> Result<Pool> {
+  let (config, tls) = config(settings)?;
+  let manager = Manager::from_config(config, tls, ManagerConfig { recycling_method: RecyclingMethod::Fast });
+  Pool::builder(manager)
+    .max_size(settings.pool_size)
+    .build()
+    .map_err(|e| error::Error::Custom(format!("couldn't build connection pool: {}", e)))
+}
+
+/// A single connection, for one-shot commands.
+pub async fn connect(settings: &Settings) -> Result<PgClient> {
+  let (config, tls) = config(settings)?;
+  let (client, connection) = config.connect(tls).await?;
+  tokio::spawn(async move {
+      if let Err(e) = connection.await {
+          eprintln!("connection error: {}", e);
+      }
+  });
+  Ok(client)
+}
+
+// An `sslmode` in `database_url` is used as given. Without one, TLS is required when a
+// `[tls]` section is configured and connections otherwise stay in plain text, since the
+// driver's default of `prefer` would fail outright against servers with certificates we
+// can't verify.
+fn config(settings: &Settings) -> Result<(Config, MakeTlsConnector)> {
+  let mut config: Config = settings.database_url.parse()?;
+  let explicit = settings.database_url.contains("sslmode=");
+  if !explicit {
+    match settings.tls {
+      Some(_) => config.ssl_mode(SslMode::Require),
+      None => config.ssl_mode(SslMode::Disable),
+    };
+  }
+
+  let mut builder = TlsConnector::builder();
+  if let Some(tls) = &settings.tls {
+    if let Some(path) = &tls.ca_cert {
+      builder.add_root_certificate(Certificate::from_pem(&read(path)?)?);
+    }
+    match (&tls.client_cert, &tls.client_key) {
+      (Some(cert), Some(key)) => {
+        builder.identity(Identity::from_pkcs8(&read(cert)?, &read(key)?)?);
+      },
+      (None, None) => (),
+      _ => return Err(error::Error::custom("tls client_cert and client_key must be set together")),
+    }
+  }
+  Ok((config, MakeTlsConnector::new(builder.build()?)))
+}
+
+fn read(path: &str) -> Result<Vec<u8>> {
+  fs::read(path).map_err(|e| error::Error::Custom(format!("couldn't read {}: {}", path, e)))
+}
@@ -12,6 +12,26 @@ pub enum Error {
   Custom(String),
   #[error("helium_jsonrpc error")]
   JrpcError(#[from] helium_jsonrpc::Error),
+  #[error("helium api error: {0}")]
+  ApiError(#[from] helium_api::Error),
+  #[error("http server error: {0}")]
+  HttpError(#[from] hyper::Error),
+  #[error("connection pool error: {0}")]
+  PoolError(#[from] deadpool_postgres::PoolError),
+  #[error("tls error: {0}")]
+  TlsError(#[from] native_tls::Error),
+  #[error("io error: {0}")]
+  IoError(#[from] std::io::Error),
+  #[error("json error: {0}")]
+  JsonError(#[from] serde_json::Error),
+  #[error("sqlite error: {0}")]
+  SqliteError(#[from] rusqlite::Error),
+  #[error("arrow error: {0}")]
+  ArrowError(#[from] arrow::error::ArrowError),
+  #[error("parquet error: {0}")]
+  ParquetError(#[from] parquet::errors::ParquetError),
+  #[error("csv error: {0}")]
+  CsvError(#[from] csv::Error),
 }
 
 impl Error {
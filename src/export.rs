@@ -0,0 +1,205 @@
+use crate::*;
+use arrow::{
+  array::{ArrayRef, Int64Array, StringArray},
+  datatypes::{DataType, Field, Schema},
+  record_batch::RecordBatch,
+};
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use futures::{pin_mut, TryStreamExt};
+use parquet::{arrow::ArrowWriter, basic::Compression, file::properties::WriterProperties};
+use slog::{info, Logger};
+use tokio_postgres::{types::ToSql, Client as PgClient, Row};
+use std::{convert::TryFrom, fs::{self, File}, path::{Path, PathBuf}, str::FromStr, sync::Arc};
+
+// Rows buffered before they are written out as a Parquet row group.
+const BATCH_ROWS: usize = 65_536;
+
+#[derive(Debug, Clone, Copy)]
+pub enum Table {
+  Rewards,
+  Transactions,
+}
+
+impl FromStr for Table {
+  type Err = String;
+
+  fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+    match s {
+      "rewards" => Ok(Table::Rewards),
+      "transactions" => Ok(Table::Transactions),
+      unsupported => Err(format!("unsupported table: \"{}\"", unsupported)),
+    }
+  }
+}
+
+struct Spec {
+  name: &'static str,
+  // $1..$2 bound the block height and $3..$4 the time, each NULL when unbounded
+  query: &'static str,
+  columns: &'static [(&'static str, DataType, bool)],
+  time_column: usize,
+  block_column: usize,
+}
+
+impl Table {
+  fn spec(&self) -> Spec {
+    match self {
+      Table::Rewards => Spec {
+        name: "rewards",
+        query: "SELECT block, transaction_hash, time, account, gateway, amount, type FROM rewards
+          WHERE ($1::bigint IS NULL OR block >= $1) AND ($2::bigint IS NULL OR block <= $2)
+            AND ($3::bigint IS NULL OR time >= $3) AND ($4::bigint IS NULL OR time < $4)
+          ORDER BY block",
+        columns: &[
+          ("block", DataType::Int64, false),
+          ("transaction_hash", DataType::Utf8, false),
+          ("time", DataType::Int64, false),
+          ("account", DataType::Utf8, false),
+          ("gateway", DataType::Utf8, false),
+          ("amount", DataType::Int64, false),
+          ("type", DataType::Utf8, false),
+        ],
+        time_column: 2,
+        block_column: 0,
+      },
+      Table::Transactions => Spec {
+        name: "transactions",
+        query: "SELECT t.block, t.hash, t.type::text AS type, b.time, t.fields::text AS fields
+          FROM transactions t LEFT JOIN blocks b ON b.height = t.block
+          WHERE ($1::bigint IS NULL OR t.block >= $1) AND ($2::bigint IS NULL OR t.block <= $2)
+            AND ($3::bigint IS NULL OR b.time >= $3) AND ($4::bigint IS NULL OR b.time < $4)
+          ORDER BY t.block",
+        columns: &[
+          ("block", DataType::Int64, false),
+          ("hash", DataType::Utf8, false),
+          ("type", DataType::Utf8, false),
+          // blocks loaded before the blocks table existed have no time
+          ("time", DataType::Int64, true),
+          ("fields", DataType::Utf8, false),
+        ],
+        time_column: 3,
+        block_column: 0,
+      },
+    }
+  }
+}
+
+/// Heights and dates to export. Both ends are inclusive and dates are UTC.
+#[derive(Debug, Default)]
+pub struct Range {
+  pub from_height: Option<u64>,
+  pub to_height: Option<u64>,
+  pub from_date: Option<NaiveDate>,
+  pub to_date: Option<NaiveDate>,
+}
+
+/// Export a table into `dir/<table>/date=YYYY-MM-DD/part-<first block>.parquet`, one
+/// partition per UTC day of block time. Rows without a block time go to `date=unknown`.
+/// Returns the number of rows exported.
+pub async fn export(pgclient: &PgClient, logger: &Logger, dir: &Path, table: Table, range: &Range) -> Result<u64> {
+  let spec = table.spec();
+  let bounds: [Option<i64>; 4] = [
+    range.from_height.map(|h| i64::try_from(h).unwrap()),
+    range.to_height.map(|h| i64::try_from(h).unwrap()),
+    range.from_date.map(|d| d.and_hms(0, 0, 0).timestamp()),
+    range.to_date.map(|d| (d + Duration::days(1)).and_hms(0, 0, 0).timestamp()),
+  ];
+  let rows = pgclient.query_raw(spec.query, bounds.iter().map(|b| b as &dyn ToSql)).await?;
+  pin_mut!(rows);
+
+  let mut out = Partitions::new(dir.join(spec.name), &spec);
+  let mut count = 0;
+  while let Some(row) = rows.try_next().await? {
+    let day = match row.get::<_, Option<i64>>(spec.time_column) {
+      Some(t) => NaiveDateTime::from_timestamp(t, 0).format("%Y-%m-%d").to_string(),
+      None => "unknown".to_string(),
+    };
+    out.push(&row, &day, row.get(spec.block_column))?;
+    count += 1;
+  }
+  out.close()?;
+
+  info!(logger, "exported {} {} rows into {} files", count, spec.name, out.files);
+  Ok(count)
+}
+
+enum Values {
+  Int64(Vec<Option<i64>>),
+  Utf8(Vec<Option<String>>),
+}
+
+// Writes rows, which arrive in block order, into one Parquet file per day.
+struct Partitions {
+  dir: PathBuf,
+  schema: Arc<Schema>,
+  values: Vec<Values>,
+  rows: usize,
+  day: Option<String>,
+  writer: Option<ArrowWriter<File>>,
+  files: usize,
+}
+
+impl Partitions {
+  fn new(dir: PathBuf, spec: &Spec) -> Self {
+    let fields = spec.columns.iter().map(|(name, t, nullable)| Field::new(name, t.clone(), *nullable)).collect();
+    let values = spec.columns.iter().map(|(_, t, _)| match t {
+      DataType::Int64 => Values::Int64(vec!()),
+      _ => Values::Utf8(vec!()),
+    }).collect();
+    Self {
+      dir,
+      schema: Arc::new(Schema::new(fields)),
+      values,
+      rows: 0,
+      day: None,
+      writer: None,
+      files: 0,
+    }
+  }
+
+  fn push(&mut self, row: &Row, day: &str, block: i64) -> Result<()> {
+    if self.day.as_deref() != Some(day) {
+      self.close()?;
+      let dir = self.dir.join(format!("date={}", day));
+      fs::create_dir_all(&dir)?;
+      let file = File::create(dir.join(format!("part-{}.parquet", block)))?;
+      let props = WriterProperties::builder().set_compression(Compression::SNAPPY).build();
+      self.writer = Some(ArrowWriter::try_new(file, self.schema.clone(), Some(props))?);
+      self.day = Some(day.to_string());
+    }
+    for (i, values) in self.values.iter_mut().enumerate() {
+      match values {
+        Values::Int64(v) => v.push(row.get(i)),
+        Values::Utf8(v) => v.push(row.get(i)),
+      }
+    }
+    self.rows += 1;
+    if self.rows >= BATCH_ROWS {
+      self.flush()?;
+    }
+    Ok(())
+  }
+
+  fn flush(&mut self) -> Result<()> {
+    let writer = match (&mut self.writer, self.rows) {
+      (Some(w), rows) if rows > 0 => w,
+      _ => return Ok(()),
+    };
+    let columns: Vec<ArrayRef> = self.values.iter_mut().map(|values| match values {
+      Values::Int64(v) => Arc::new(Int64Array::from(std::mem::take(v))) as ArrayRef,
+      Values::Utf8(v) => Arc::new(std::mem::take(v).iter().map(|s| s.as_deref()).collect::<StringArray>()) as ArrayRef,
+    }).collect();
+    writer.write(&RecordBatch::try_new(self.schema.clone(), columns)?)?;
+    self.rows = 0;
+    Ok(())
+  }
+
+  fn close(&mut self) -> Result<()> {
+    self.flush()?;
+    if let Some(writer) = self.writer.take() {
+      writer.close()?;
+      self.files += 1;
+    }
+    Ok(())
+  }
+}
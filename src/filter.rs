@@ -1,9 +1,47 @@
 use crate::*;
-use tokio_postgres::{Client, Statement};
+use slog::{error, info, Logger};
+use tokio_postgres::{Client, Row, Statement};
+use std::{collections::HashSet, convert::TryFrom};
 
+#[derive(Debug, Default, PartialEq)]
 pub struct Filters {
-  pub accounts: Vec<String>,
-  pub gateways: Vec<String>,
+  pub accounts: HashSet<String>,
+  pub gateways: HashSet<String>,
+  pub owners: HashSet<String>,
+  // gateways currently owned by one of `owners`
+  pub owned_gateways: HashSet<String>,
+}
+
+impl Filters {
+  pub fn has_account(&self, account: &str) -> bool {
+    self.accounts.contains(account)
+  }
+
+  pub fn has_gateway(&self, gateway: &str) -> bool {
+    self.gateways.contains(gateway) || self.owned_gateways.contains(gateway)
+  }
+
+  pub fn has_address(&self, address: &str) -> bool {
+    self.has_account(address) || self.has_gateway(address)
+  }
+
+  /// The filters whose rewards at `height` are loaded by the follower or a finished
+  /// backfill, as opposed to ones the backfill still has to load.
+  pub fn covering(states: &[FilterState], height: u64) -> Filters {
+    let mut filters = Filters::default();
+    for state in states.iter().filter(|s| s.covers(height)) {
+      match state.r#type.as_str() {
+        "account" => { filters.accounts.insert(state.value.clone()); },
+        "gateway" => { filters.gateways.insert(state.value.clone()); },
+        "owner" => {
+          filters.owners.insert(state.value.clone());
+          filters.owned_gateways.extend(state.owned_gateways.iter().cloned());
+        },
+        _ => (),
+      }
+    }
+    filters
+  }
 }
 
 pub async fn prepare(client: &Client) -> Result<Statement>{
@@ -20,20 +58,173 @@ pub async fn get(client: &Client) -> Result<Filters>{
     Ok(rows) => rows,
     Err(e) => {
       println!("{}", e);
-      return Err(error::Error::PgError(e))      
+      return Err(error::Error::PgError(e))
     },
   };
-  let accounts: Vec<String> = filter_rows
-    .iter()
-    .filter(|f| f.get::<_, String>("type") == "account")
-    .map(|f| f.get::<_, String>("value"))
-    .collect();
-
-  let gateways: Vec<String> = filter_rows
-    .iter()
-    .filter(|f| f.get::<_, String>("type") == "gateway")
-    .map(|f| f.get::<_, String>("value"))
-    .collect();
-
-  Ok(Filters{accounts: accounts, gateways: gateways})
-}
\ No newline at end of file
+  from_rows(client, &filter_rows).await
+}
+
+/// The filters picked up at or before `height`. Filters inserted after the last
+/// `mark_added` are left for the next refresh, so each one is loaded by the follower
+/// strictly after its `added_height` and by the backfill up to it.
+pub async fn get_added(client: &Client, height: u64) -> Result<Filters> {
+  let filter_rows = client.query("SELECT type::varchar(255), value FROM filters WHERE added_height <= $1",
+    &[&i64::try_from(height).unwrap()]).await?;
+  from_rows(client, &filter_rows).await
+}
+
+async fn from_rows(client: &Client, filter_rows: &[Row]) -> Result<Filters> {
+  let values = |r#type: &str| -> HashSet<String> {
+    filter_rows
+      .iter()
+      .filter(|f| f.get::<_, String>("type") == r#type)
+      .map(|f| f.get::<_, String>("value"))
+      .collect()
+  };
+
+  let accounts = values("account");
+  let gateways = values("gateway");
+  let owners = values("owner");
+  let owned_gateways = ownership::owned_gateways(client, &owners).await?;
+
+  Ok(Filters{accounts: accounts, gateways: gateways, owners: owners, owned_gateways: owned_gateways})
+}
+
+/// Record `height` as the height newly inserted filters were picked up at. Rewards up to
+/// that height are loaded by the backfill, everything after by the follower. New owner
+/// filters are only picked up once their existing hotspots are recorded from the Helium
+/// API, and are retried on the next refresh if that fails.
+pub async fn mark_added(client: &Client, api: &helium_api::Client, logger: &Logger, height: u64) -> Result<u64> {
+  let rows = client.query("SELECT value FROM filters WHERE type = 'owner' AND added_height IS NULL", &[]).await?;
+  let mut seeded: Vec<String> = vec!();
+  for r in &rows {
+    let owner: String = r.get(0);
+    match ownership::seed_owner(client, api, &owner).await {
+      Ok(n) => {
+        info!(logger, "recorded {} existing hotspots owned by {}", n, owner);
+        seeded.push(owner);
+      },
+      Err(e) => error!(logger, "couldn't get hotspots owned by {}, will retry: {}", owner, e),
+    }
+  }
+
+  client.execute("UPDATE filters SET added_height = $1 WHERE added_height IS NULL AND (type <> 'owner' OR value = ANY($2))",
+    &[&i64::try_from(height).unwrap(), &seeded])
+    .await
+    .map_err(|e| error::Error::PgError(e))
+}
+
+/// Re-read the filters table, replacing `filters` and logging the new set if it changed.
+/// Returns whether anything changed.
+pub async fn refresh(client: &Client, api: &helium_api::Client, logger: &Logger, height: u64, filters: &mut Filters) -> Result<bool> {
+  mark_added(client, api, logger, height).await?;
+  let latest = get_added(client, height).await?;
+  if &latest == filters {
+    return Ok(false);
+  }
+  info!(logger, "filters changed, now loading accounts: {:?} gateways: {:?} owners: {:?} ({} gateways owned)",
+    latest.accounts, latest.gateways, latest.owners, latest.owned_gateways.len());
+  *filters = latest;
+  Ok(true)
+}
+
+/// A filter along with how far its backfill has progressed.
+#[derive(Debug)]
+pub struct FilterState {
+  pub r#type: String,
+  pub value: String,
+  pub added_height: Option<u64>,
+  pub backfill_height: Option<u64>,
+  // for owner filters, the gateways the owner currently holds
+  pub owned_gateways: HashSet<String>,
+}
+
+impl FilterState {
+  pub fn matches(&self, account: Option<&str>, gateway: Option<&str>) -> bool {
+    match self.r#type.as_str() {
+      "account" => account == Some(self.value.as_str()),
+      "gateway" => gateway == Some(self.value.as_str()),
+      "owner" => gateway.map_or(false, |g| self.owned_gateways.contains(g)),
+      _ => false,
+    }
+  }
+
+  /// Whether rewards at `height` are already loaded for this filter, either by the
+  /// follower or by a previous backfill.
+  pub fn covers(&self, height: u64) -> bool {
+    match self.added_height {
+      Some(added) => height > added || self.backfill_height.map_or(false, |b| height <= b),
+      None => false,
+    }
+  }
+}
+
+pub async fn get_states(client: &Client) -> Result<Vec<FilterState>> {
+  let rows = client.query("SELECT type::varchar(255), value, added_height, backfill_height FROM filters", &[]).await?;
+  let mut states = vec!();
+  for r in &rows {
+    let r#type: String = r.get("type");
+    let value: String = r.get("value");
+    let owned_gateways = match r#type.as_str() {
+      "owner" => {
+        let owner: HashSet<String> = std::iter::once(value.clone()).collect();
+        ownership::owned_gateways(client, &owner).await?
+      },
+      _ => HashSet::new(),
+    };
+    states.push(FilterState {
+      r#type,
+      value,
+      added_height: r.get::<_, Option<i64>>("added_height").map(|h| u64::try_from(h).unwrap()),
+      backfill_height: r.get::<_, Option<i64>>("backfill_height").map(|h| u64::try_from(h).unwrap()),
+      owned_gateways,
+    });
+  }
+  Ok(states)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn state(added_height: Option<u64>, backfill_height: Option<u64>) -> FilterState {
+    FilterState {
+      r#type: "account".to_string(),
+      value: "account1".to_string(),
+      added_height,
+      backfill_height,
+      owned_gateways: HashSet::new(),
+    }
+  }
+
+  #[test]
+  fn unstamped_filter_covers_nothing() {
+    let f = state(None, None);
+    assert!(!f.covers(1));
+    assert!(!f.covers(100));
+  }
+
+  #[test]
+  fn follower_covers_heights_above_added() {
+    let f = state(Some(10), None);
+    assert!(!f.covers(1));
+    assert!(!f.covers(10));
+    assert!(f.covers(11));
+  }
+
+  #[test]
+  fn backfill_covers_heights_up_to_its_progress() {
+    let f = state(Some(10), Some(4));
+    assert!(f.covers(1));
+    assert!(f.covers(4));
+    assert!(!f.covers(5));
+    assert!(!f.covers(10));
+    assert!(f.covers(11));
+  }
+
+  #[test]
+  fn finished_backfill_covers_everything() {
+    let f = state(Some(10), Some(10));
+    assert!((1..=20).all(|h| f.covers(h)));
+  }
+}
@@ -1,40 +1,47 @@
 use crate::*;
-use slog::{error, info, o, Logger};
-use helium_jsonrpc::{ Client, blocks, transactions, Transaction };
-use tokio_postgres::{ Client as PgClient };
-use std::convert::TryFrom;
-use crate::block_processor::BlockProcessor;
+use slog::{error, info, o, warn, Logger};
+use helium_jsonrpc::Transaction;
+use deadpool_postgres::Pool;
+use std::{convert::TryFrom, time::{Duration, Instant}};
+use futures::stream::{self, StreamExt};
+use crate::block_processor::{self, BlockProcessor};
+use crate::sink::{Position, Sink};
 
 pub struct Follower {
   mode: EtlMode,
   pub height: u64,
+  hash: Option<String>,
   first_block: u64,
-  client: Client,
-  pgclient: PgClient,
+  prefetch: usize,
+  client: rpc::NodeClient,
+  // Helium API, for the hotspots new owner filters already hold
+  api: helium_api::Client,
+  sink: Box<dyn Sink>,
+  // Postgres, for the filters table in filters mode
+  pool: Option<Pool>,
   shutdown: triggered::Listener,
   logger: Logger,
   filters: filter::Filters,
-}
-
-pub struct Info {
-  height: u64,
-  first_block: u64,
+  filters_refreshed: Instant,
+  filter_refresh: Duration,
 }
 
 impl Follower {
-  pub async fn new(settings: &Settings, pgclient: PgClient, logger: &Logger, shutdown: triggered::Listener) -> Result<Self> {
-    let client = Client::new_with_base_url(settings.node_addr.to_string());
-    let info = match load_follower_info(&logger, &pgclient).await {
-      Ok(i) => i,
-      Err(_) => {
+  pub async fn new(settings: &Settings, mut sink: Box<dyn Sink>, pool: Option<Pool>, logger: &Logger, shutdown: triggered::Listener) -> Result<Self> {
+    let client = rpc::NodeClient::new(settings);
+    let position = match sink.position().await? {
+      Some(p) => p,
+      None => {
         let first = match settings.backfill {
           true => get_first_block(&client, &logger, shutdown.clone()).await.unwrap(),
-          false => blocks::height(&client).await?
+          false => client.height().await?
         };
         
-        create_follower_info(&logger, &pgclient, first).await.unwrap();
-        Info {
+        info!(logger, "Starting sink at block {}", first);
+        sink.start(first).await?;
+        Position {
           height: first-1,
+          hash: None,
           first_block: first,
         }       
       }
@@ -46,104 +53,172 @@ impl Follower {
       EtlMode::Filters => logger.new(o!("module" => "FiltersMode")),
     };
 
-    let filters = match settings.mode {
-      EtlMode::Filters => {
-        match filter::get(&pgclient).await {
+    let api = ownership::api_client(settings);
+    let filters = match (settings.mode, &pool) {
+      (EtlMode::Filters, Some(pool)) => {
+        let pgclient = pool.get().await?;
+        filter::mark_added(&pgclient, &api, &logger, position.height).await?;
+        match filter::get_added(&pgclient, position.height).await {
           Ok(f) => f,
           Err(e) => panic!("problem getting filters: {}", e),
         }
       },
-      _ => filter::Filters{ accounts: vec!(), gateways: vec!() },
+      (EtlMode::Filters, None) => return Err(error::Error::custom("filters mode needs the postgres sink")),
+      _ => filter::Filters::default(),
     };
     Ok(Self {
       mode: settings.mode,
-      height: info.height,
-      first_block: info.first_block,
+      height: position.height,
+      hash: position.hash,
+      first_block: position.first_block,
+      prefetch: settings.prefetch.max(1),
       client: client,
-      pgclient: pgclient,
+      api: api,
+      sink: sink,
+      pool: pool,
       shutdown: shutdown,
       logger: logger,
       filters: filters,
+      filters_refreshed: Instant::now(),
+      filter_refresh: Duration::from_secs(settings.filter_refresh_secs),
     })
   }
   pub async fn run(&mut self) {
     loop {
+      // resume from what was actually written, in case the last run failed mid-block.
+      // With Postgres this also takes a fresh connection, recovering from database restarts.
+      match self.sink.position().await {
+        Ok(Some(position)) => {
+          health::set_database_ok(true);
+          self.height = position.height;
+          self.hash = position.hash;
+        },
+        Ok(None) => {
+          error!(self.logger, "sink has no position");
+          return
+        },
+        Err(e) => {
+          error!(self.logger, "Couldn't load position from sink: {}", e);
+          metrics::error("database");
+          health::set_database_ok(false);
+          return
+        }
+      }
+
       tokio::select! {
         _ = self.shutdown.clone() => {
           info!(self.logger, "shutting down Follower at height: {}", self.height);
           return
         },
-        maybe_current_height = blocks::height(&self.client) => {
+        maybe_current_height = self.client.height() => {
           let current_height = match maybe_current_height {
             Ok(ch) => ch,
             Err(e) => {
               error!(self.logger, "Couldn't get height from node: {}", e);
+              metrics::error("node_height");
+              health::set_node_ok(false);
               return
             }
           };
+          health::set_node_ok(true);
+          metrics::NODE_HEIGHT.set(i64::try_from(current_height).unwrap());
+          metrics::HEIGHT_LAG.set(i64::try_from(current_height.saturating_sub(self.height)).unwrap());
 
           match current_height {
-            h if h > self.height => match self.start_block_processing().await {
+            h if h > self.height => match self.process_blocks(h).await {
               Ok(_) => (),
               Err(e) => {
                 error!(self.logger, "error processing block: {}", e);
+                metrics::error("block_processing");
                 return
               },
             },
-            _ => return
+            _ => {
+              health::progressed();
+              return
+            }
           }
         }
       }
     }
   }
-  pub async fn start_block_processing(&mut self) -> Result<()> {
-    let pgtran = match self.pgclient.build_transaction().start().await {
-      Ok(t) => t,
-      Err(e) => {
-        error!(self.logger, "Couldn't start database transaction: {}", e);
-        return Err(error::Error::custom(e.to_string()));
+  /// Load every block up to `current_height`. Up to `prefetch` blocks are fetched
+  /// from the node concurrently, but each one is committed strictly in height order.
+  pub async fn process_blocks(&mut self, current_height: u64) -> Result<()> {
+    let mode = self.mode;
+    let client = &self.client;
+    let started = Instant::now();
+    let start_height = self.height;
+    let mut fetched = stream::iter(self.height + 1..=current_height)
+      .map(|height| block_processor::fetch(mode, client, height))
+      .buffered(self.prefetch);
+
+    while let Some(block) = fetched.next().await {
+      if self.shutdown.is_triggered() {
+        return Ok(());
       }
-    };
+      let block = block?;
 
-    BlockProcessor::new(self.mode, self.height + 1, &self.client, pgtran, &self.logger, &self.filters).process().await?;
-    self.height += 1;
+      if let EtlMode::Filters = mode {
+        if self.filters_refreshed.elapsed() >= self.filter_refresh {
+          refresh_filters(&self.pool, &self.api, &self.logger, self.height, &mut self.filters).await?;
+          self.filters_refreshed = Instant::now();
+        }
+      }
 
-    Ok(())
-  }
-}
+      if let Some(hash) = &self.hash {
+        if &block.block.prev_hash != hash {
+          warn!(self.logger, "block {} prev_hash {} doesn't match last committed hash {}",
+            block.height(), block.block.prev_hash, hash);
+          drop(fetched);
+          return self.rollback().await;
+        }
+      }
 
-pub async fn create_follower_info(logger: &Logger, pgclient: &PgClient, first_block: u64) -> Result<Vec<tokio_postgres::Row>> {
-  let stmt = pgclient.prepare("INSERT INTO follower_info (height, first_block) VALUES ($1, $2)").await?;
-  info!(logger, "Adding follower info to database");
-  pgclient.query(&stmt, &[&i64::try_from(first_block).unwrap(), &i64::try_from(first_block).unwrap()])
-    .await
-    .map_err(|e| error::Error::PgError(e))
-}
+      let height = block.height();
+      let hash = block.block.hash.to_string();
+      let owners_changed = !self.filters.owners.is_empty()
+        && block.transactions.iter().any(|t| ownership::owner_change(&t.transaction).is_some());
+      BlockProcessor::new(mode, block, &self.logger, &self.filters).process(self.sink.as_mut()).await?;
+      self.height = height;
+      self.hash = Some(hash);
+      health::progressed();
 
-pub async fn load_follower_info(logger: &Logger, pgclient: &PgClient) -> Result<Info> {
-  let stmt = pgclient.prepare("SELECT height, first_block FROM follower_info").await?;
-  let info_rows = pgclient.query(&stmt, &[]).await?;
+      // owner filters follow their hotspots as they are added or transferred
+      if owners_changed {
+        refresh_filters(&self.pool, &self.api, &self.logger, self.height, &mut self.filters).await?;
+      }
 
-  match info_rows.len() {
-    0 => {
-      info!(logger, "no follower_info data");
-      Err(error::Error::Custom("no follower info".to_string()))
+      metrics::HEIGHT_LAG.set(i64::try_from(current_height.saturating_sub(self.height)).unwrap());
+      metrics::BLOCKS_PER_SECOND.set((self.height - start_height) as f64 / started.elapsed().as_secs_f64());
     }
-    _ => {
-      let height: i64 = info_rows[0].get(0);
-      let first_block: i64 = info_rows[0].get(1);
-      Ok(Info {
-        height: u64::try_from(height).unwrap(),
-        first_block: u64::try_from(first_block).unwrap(),
-      })
 
-    }
+    Ok(())
+  }
+
+  /// Find the last block that still matches the node and drop everything loaded above it,
+  /// so the following run re-ingests the node's version of the chain.
+  async fn rollback(&mut self) -> Result<()> {
+    let fork = reorg::find_fork_point(&self.client, self.sink.as_mut(), &self.logger, self.height, self.first_block).await?;
+    warn!(self.logger, "rolling back from height {} to fork point {}", self.height, fork);
+    self.hash = self.sink.rollback(fork).await?;
+    self.height = fork;
+    metrics::HEIGHT.set(i64::try_from(fork).unwrap());
+    metrics::error("reorg");
+    Ok(())
+  }
+}
+
+async fn refresh_filters(pool: &Option<Pool>, api: &helium_api::Client, logger: &Logger, height: u64, filters: &mut filter::Filters) -> Result<bool> {
+  match pool {
+    Some(pool) => filter::refresh(&pool.get().await?, api, logger, height, filters).await,
+    None => Ok(false),
   }
 }
 
-pub async fn get_first_block(client: &Client, logger: &Logger, shutdown: triggered::Listener) -> Result<u64> {
+pub async fn get_first_block(client: &rpc::NodeClient, logger: &Logger, shutdown: triggered::Listener) -> Result<u64> {
   info!(logger, "Scanning blocks by epoch to get first block on node.");
-  let mut height = blocks::height(&client).await?;
+  let mut height = client.height().await?;
   let mut last_safe_height = height;
   let mut in_last_epoch = false;
 
@@ -153,14 +228,14 @@ pub async fn get_first_block(client: &Client, logger: &Logger, shutdown: trigger
         info!(logger, "abandoning get_first_height at height: {}", last_safe_height);
         return Ok(last_safe_height)
       },
-      blockraw = blocks::get_raw(&client, &height) => {
+      blockraw = client.get_block(height) => {
         let block = match blockraw {
           Ok(b) => b,
           Err(_) if in_last_epoch => return Ok(last_safe_height),
           Err(_) => {
             in_last_epoch = true;
             height = last_safe_height - 1;
-            match blocks::get_raw(&client, &height).await {
+            match client.get_block(height).await {
               Ok(b) => b,
               Err(e) => panic!("Can't get last height, stuck on block {}: {}", height, e),
             }
@@ -170,7 +245,7 @@ pub async fn get_first_block(client: &Client, logger: &Logger, shutdown: trigger
           match txn.r#type.as_str() {
             "rewards_v2" => {
               info!(&logger, "Getting start_epoch from block {}", height);
-              match transactions::get(&client, &txn.hash).await {
+              match client.get_transaction(&txn.hash).await {
                 Ok(t) => {
                   match t {
                     Transaction::RewardsV2(rewards) => height = rewards.start_epoch,
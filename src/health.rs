@@ -0,0 +1,58 @@
+use lazy_static::lazy_static;
+use std::{
+  sync::{atomic::{AtomicBool, Ordering}, Mutex},
+  time::{Duration, Instant},
+};
+
+/// Follower health as reported on `/healthz` and `/ready`. Updated by the follower as it
+/// talks to the node and the database.
+pub struct Health {
+  last_progress: Mutex<Instant>,
+  node_ok: AtomicBool,
+  database_ok: AtomicBool,
+}
+
+lazy_static! {
+  pub static ref HEALTH: Health = Health {
+    last_progress: Mutex::new(Instant::now()),
+    node_ok: AtomicBool::new(false),
+    database_ok: AtomicBool::new(false),
+  };
+}
+
+/// Record that the follower committed a block or is caught up with the node.
+pub fn progressed() {
+  *HEALTH.last_progress.lock().unwrap() = Instant::now();
+}
+
+pub fn set_node_ok(ok: bool) {
+  HEALTH.node_ok.store(ok, Ordering::Relaxed);
+}
+
+pub fn set_database_ok(ok: bool) {
+  HEALTH.database_ok.store(ok, Ordering::Relaxed);
+}
+
+/// Whether the follower has made progress within `stall_timeout`.
+pub fn is_healthy(stall_timeout: Duration) -> bool {
+  HEALTH.last_progress.lock().unwrap().elapsed() < stall_timeout
+}
+
+/// Whether the follower is healthy and can reach both the node and Postgres.
+/// Returns a description of every failing check otherwise.
+pub fn readiness(stall_timeout: Duration) -> std::result::Result<(), Vec<&'static str>> {
+  let mut failing = vec!();
+  if !is_healthy(stall_timeout) {
+    failing.push("follower has not advanced");
+  }
+  if !HEALTH.node_ok.load(Ordering::Relaxed) {
+    failing.push("blockchain-node unreachable");
+  }
+  if !HEALTH.database_ok.load(Ordering::Relaxed) {
+    failing.push("postgres unreachable");
+  }
+  match failing.is_empty() {
+    true => Ok(()),
+    false => Err(failing),
+  }
+}
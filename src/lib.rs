@@ -1,11 +1,29 @@
 pub mod error;
 pub mod settings;
+pub mod db;
+pub mod logger;
 pub mod follower;
+pub mod rpc;
 pub mod block_processor;
+pub mod block;
 pub mod migrate;
 pub mod reward;
 pub mod transaction;
+pub mod typed_transaction;
+pub mod actor;
 pub mod filter;
+pub mod ownership;
+pub mod oracle;
+pub mod reorg;
+pub mod sink;
+pub mod verify;
+pub mod loader;
+pub mod backfill;
+pub mod export;
+pub mod csv_export;
+pub mod metrics;
+pub mod health;
+pub mod server;
 
 pub use error::{Error, Result};
 pub use settings::{EtlMode, Settings};
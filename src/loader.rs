@@ -0,0 +1,49 @@
+use crate::*;
+use crate::block_processor::{self, BlockProcessor};
+use slog::{info, Logger};
+use tokio_postgres::{ Client as PgClient };
+use futures::stream::{self, StreamExt};
+use std::convert::TryFrom;
+
+/// The height of the last block committed by the follower.
+pub async fn committed_height(pgclient: &PgClient) -> Result<u64> {
+  let rows = pgclient.query("SELECT height FROM follower_info", &[]).await?;
+  match rows.first() {
+    Some(r) => Ok(u64::try_from(r.get::<_, i64>(0)).unwrap()),
+    None => Err(error::Error::custom("no follower info, run start first")),
+  }
+}
+
+/// Re-ingest the given heights from the node, replacing anything already stored for them.
+/// Blocks are fetched concurrently like the follower does but follower_info is not touched,
+/// so heights must not be above `committed_height`.
+pub async fn load_heights<I>(settings: &Settings, pgclient: &mut PgClient, logger: &Logger, heights: I) -> Result<()>
+where
+  I: IntoIterator<Item = u64>,
+{
+  let client = rpc::NodeClient::new(settings);
+  // heights a filter's backfill hasn't reached yet are left to the backfill, which
+  // would otherwise load their rewards a second time
+  let states = match settings.mode {
+    EtlMode::Filters => filter::get_states(pgclient).await?,
+    _ => vec!(),
+  };
+
+  let mode = settings.mode;
+  let client = &client;
+  let mut fetched = stream::iter(heights)
+    .map(|height| block_processor::fetch(mode, client, height))
+    .buffered(settings.prefetch.max(1));
+
+  let mut loaded = 0;
+  while let Some(block) = fetched.next().await {
+    let block = block?;
+    let filters = filter::Filters::covering(&states, block.height());
+    let pgtran = pgclient.build_transaction().start().await?;
+    BlockProcessor::new(mode, block, logger, &filters).reload(pgtran).await?;
+    loaded += 1;
+  }
+
+  info!(logger, "loaded {} blocks", loaded);
+  Ok(())
+}
@@ -0,0 +1,68 @@
+use crate::*;
+use crate::settings::{Log, LogFormat, LogOutput, LogRotation};
+use file_rotate::{compression::Compression, suffix::{AppendTimestamp, FileLimit}, ContentLimit, FileRotate, TimeFrequency};
+use slog::{o, Drain, Logger};
+use slog_async::{Async, AsyncGuard};
+use std::{fs, io::{self, Write}};
+
+/// Logger for `start`, configured by the `[log]` settings. Log files are appended to
+/// and rotated, never truncated. Dropping the returned guard flushes queued records.
+pub fn start(settings: &Log) -> Result<(Logger, AsyncGuard)> {
+  let writer: Box<dyn Write + Send> = match settings.output {
+    LogOutput::Stdout => Box::new(io::stdout()),
+    LogOutput::File => {
+      fs::create_dir_all(&settings.log_dir)
+        .map_err(|e| error::Error::Custom(format!("couldn't create log dir {}: {}", settings.log_dir, e)))?;
+      let limit = match settings.rotation {
+        LogRotation::Size => ContentLimit::BytesSurpassed(settings.max_size_mb * 1024 * 1024),
+        LogRotation::Hourly => ContentLimit::Time(TimeFrequency::Hourly),
+        LogRotation::Daily => ContentLimit::Time(TimeFrequency::Daily),
+      };
+      Box::new(FileRotate::new(
+        format!("{}/etl_lite.log", settings.log_dir),
+        AppendTimestamp::default(FileLimit::MaxFiles(settings.max_files)),
+        limit,
+        Compression::None,
+        #[cfg(unix)]
+        None,
+      ))
+    },
+  };
+  let (drain, guard) = async_drain(writer, settings.format);
+  // ignore rather than panic on records logged after the guard has been dropped
+  let logger = slog::Logger::root(drain.ignore_res().filter_level(settings.level).fuse(), o!());
+  Ok((logger, guard))
+}
+
+/// Logger for one-shot subcommands, so they don't write to the follower's log file.
+pub fn terminal() -> Logger {
+  let decorator = slog_term::TermDecorator::new().stdout().build();
+  let drain = slog_term::FullFormat::new(decorator)
+    .use_custom_timestamp(slog_term::timestamp_local)
+    .build()
+    .fuse();
+  let async_drain = slog_async::Async::new(drain)
+    .build()
+    .fuse();
+  slog::Logger::root(async_drain, o!())
+}
+
+fn async_drain(writer: Box<dyn Write + Send>, format: LogFormat) -> (Async, AsyncGuard) {
+  match format {
+    LogFormat::Text => {
+      let decorator = slog_term::PlainDecorator::new(writer);
+      let drain = slog_term::FullFormat::new(decorator)
+        .use_custom_timestamp(slog_term::timestamp_local)
+        .build()
+        .fuse();
+      Async::new(drain).build_with_guard()
+    },
+    LogFormat::Json => {
+      let drain = slog_json::Json::new(writer)
+        .add_default_keys()
+        .build()
+        .fuse();
+      Async::new(drain).build_with_guard()
+    },
+  }
+}
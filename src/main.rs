@@ -1,17 +1,30 @@
 use helium_etl_lite::{
-  settings::Settings,
+  settings::{EtlMode, Settings},
+  db,
+  sink,
   follower::Follower,
+  backfill::Backfill,
+  server,
   migrate,
+  verify,
+  loader,
+  logger,
+  export,
+  csv_export,
 };
-use slog::{self, o, Drain, Logger, info};
-use std::{fs, fs::OpenOptions};
+use chrono::NaiveDate;
+use slog::{error, info, Logger};
+use std::path::PathBuf;
 use structopt::StructOpt;
 use tokio::time;
-use tokio_postgres::{Client as PgClient, NoTls};
+use deadpool_postgres::Pool;
 
 #[derive(Debug, StructOpt)]
 #[structopt(name = "Helium Blockchain ETL Lite", about = "A Light ETL for the Helium Blockchain")]
 pub struct Cli {
+  /// Settings file to load. Any setting can be overridden with an ETL_ environment variable
+  #[structopt(long, global = true, default_value = "config/settings.toml", parse(from_os_str))]
+  config: PathBuf,
   #[structopt(subcommand)]
   cmd: Cmd,
 }
@@ -20,70 +33,257 @@ pub struct Cli {
 pub enum Cmd {
   Start,
   Migrate,
+  /// Scan the database for missing or partially-loaded blocks
+  Verify,
+  /// Re-ingest every block reported by `verify` from the node
+  Repair,
+  /// Ingest an inclusive range of heights from the node without touching follower_info
+  Load {
+    #[structopt(long)]
+    from: u64,
+    #[structopt(long)]
+    to: u64,
+  },
+  /// Export rewards and transactions to Parquet files partitioned by day
+  Export {
+    /// Directory to write <table>/date=YYYY-MM-DD/part-<block>.parquet files into
+    #[structopt(long, default_value = "export", parse(from_os_str))]
+    out: PathBuf,
+    /// "rewards" or "transactions". Both are exported when left out
+    #[structopt(long)]
+    table: Option<export::Table>,
+    #[structopt(long)]
+    from_height: Option<u64>,
+    #[structopt(long)]
+    to_height: Option<u64>,
+    /// First UTC day to export, as YYYY-MM-DD
+    #[structopt(long)]
+    from_date: Option<NaiveDate>,
+    /// Last UTC day to export, as YYYY-MM-DD
+    #[structopt(long)]
+    to_date: Option<NaiveDate>,
+  },
+  /// Export rewards of accounts or gateways to CSV, valued in USD at the oracle price
+  ExportCsv {
+    /// Directory to write account-<address>.csv and gateway-<address>.csv files into
+    #[structopt(long, default_value = "export", parse(from_os_str))]
+    out: PathBuf,
+    /// Account to export, can be repeated. Defaults to every filter in the filters table
+    #[structopt(long)]
+    account: Vec<String>,
+    /// Gateway to export, can be repeated. Defaults to every filter in the filters table
+    #[structopt(long)]
+    gateway: Vec<String>,
+    #[structopt(long)]
+    from_height: Option<u64>,
+    #[structopt(long)]
+    to_height: Option<u64>,
+    /// First UTC day to export, as YYYY-MM-DD
+    #[structopt(long)]
+    from_date: Option<NaiveDate>,
+    /// Last UTC day to export, as YYYY-MM-DD
+    #[structopt(long)]
+    to_date: Option<NaiveDate>,
+  },
+  /// Inspect the effective settings
+  Config(ConfigCmd),
+}
+
+#[derive(Debug, StructOpt)]
+pub enum ConfigCmd {
+  /// Validate the settings and print them with secrets redacted
+  Check,
 }
 
 #[tokio::main]
 async fn main() {
   let cli = Cli::from_args();
-  let settings = Settings::new().unwrap();
+  let settings = match Settings::new(&cli.config) {
+    Ok(s) => s,
+    Err(e) => {
+      eprintln!("invalid settings in {}: {:?}", cli.config.display(), e);
+      std::process::exit(1);
+    }
+  };
   match cli.cmd {
     Cmd::Start => {
-      let (client, connection) = tokio_postgres::connect(&settings.database_url, NoTls).await.unwrap();
-      tokio::spawn(async move {
-          if let Err(e) = connection.await {
-              eprintln!("connection error: {}", e);
-          }
-      });     
-      run(&settings, client).await;
+      let pool = match settings.uses_postgres() {
+        true => Some(db::pool(&settings).unwrap()),
+        false => None,
+      };
+      let code = run(&settings, pool).await;
+      std::process::exit(code);
     },
     Cmd::Migrate => {
       migrate::run(&settings).await;
       return
     },
+    Cmd::Verify => {
+      let client = db::connect(&settings).await.unwrap();
+      let gaps = verify::find_gaps(&client, settings.mode).await.unwrap();
+      for gap in &gaps {
+        println!("{}", gap);
+      }
+      println!("{} gaps found", gaps.len());
+      if !gaps.is_empty() {
+        std::process::exit(1);
+      }
+    },
+    Cmd::Repair => {
+      let mut client = db::connect(&settings).await.unwrap();
+      let logger = logger::terminal();
+      let gaps = verify::find_gaps(&client, settings.mode).await.unwrap();
+      let mut heights: Vec<u64> = gaps.iter().map(|g| g.height).collect();
+      heights.dedup();
+      info!(logger, "repairing {} blocks", heights.len());
+      loader::load_heights(&settings, &mut client, &logger, heights).await.unwrap();
+    },
+    Cmd::Load { from, to } => {
+      if from > to {
+        eprintln!("--from ({}) must not be greater than --to ({})", from, to);
+        std::process::exit(1);
+      }
+      let mut client = db::connect(&settings).await.unwrap();
+      // the follower inserts heights above this itself, loading them here would collide
+      let height = loader::committed_height(&client).await.unwrap();
+      if to > height {
+        eprintln!("--to ({}) must not be greater than the follower's height ({})", to, height);
+        std::process::exit(1);
+      }
+      let logger = logger::terminal();
+      loader::load_heights(&settings, &mut client, &logger, from..=to).await.unwrap();
+    },
+    Cmd::Export { out, table, from_height, to_height, from_date, to_date } => {
+      let client = db::connect(&settings).await.unwrap();
+      let logger = logger::terminal();
+      let range = export::Range { from_height, to_height, from_date, to_date };
+      let tables = match table {
+        Some(t) => vec![t],
+        None => vec![export::Table::Rewards, export::Table::Transactions],
+      };
+      for table in tables {
+        export::export(&client, &logger, &out, table, &range).await.unwrap();
+      }
+    },
+    Cmd::ExportCsv { out, account, gateway, from_height, to_height, from_date, to_date } => {
+      // addresses end up in file names
+      if let Some(address) = account.iter().chain(gateway.iter()).find(|a| !csv_export::is_address(a)) {
+        eprintln!("{} is not a base58 address", address);
+        std::process::exit(1);
+      }
+      let client = db::connect(&settings).await.unwrap();
+      let logger = logger::terminal();
+      let range = export::Range { from_height, to_height, from_date, to_date };
+      let mut targets: Vec<csv_export::Target> = account.into_iter().map(csv_export::Target::Account)
+        .chain(gateway.into_iter().map(csv_export::Target::Gateway))
+        .collect();
+      if targets.is_empty() {
+        targets = csv_export::filter_targets(&client).await.unwrap();
+      }
+      for target in &targets {
+        csv_export::export(&client, &logger, &out, target, &range).await.unwrap();
+      }
+    },
+    Cmd::Config(ConfigCmd::Check) => {
+      // building the pool parses database_url and loads any TLS files without connecting
+      if let (true, Err(e)) = (settings.uses_postgres(), db::pool(&settings)) {
+        eprintln!("invalid settings in {}: {}", cli.config.display(), e);
+        std::process::exit(1);
+      }
+      println!("{}", settings);
+    },
   }  
 
-  pub async fn run(settings: &Settings, client: PgClient) {
-    let logger = start_logger(&settings);
+  // Returns the process exit status: 0 after a clean shutdown, 1 if the follower
+  // couldn't start.
+  pub async fn run(settings: &Settings, pool: Option<Pool>) -> i32 {
+    let (logger, log_guard) = match logger::start(&settings.log) {
+      Ok(l) => l,
+      Err(e) => {
+        eprintln!("couldn't start logger: {}", e);
+        return 1
+      }
+    };
     info!(logger, "hello!");
     let (shutdown_trigger, shutdown_listener) = triggered::trigger();
+    let signal_logger = logger.clone();
     tokio::spawn(async move {
-        let _ = tokio::signal::ctrl_c().await;
+        shutdown_signal(&signal_logger).await;
         shutdown_trigger.trigger();
-    }); 
-    let mut follower = Follower::new(&settings, client, &logger, shutdown_listener.clone()).await.unwrap();
+    });
+    let code = follow(settings, pool, &logger, shutdown_listener).await;
+    info!(logger, "Goodbye!");
+    // flush everything still queued in the async drain before the process exits
+    drop(logger);
+    drop(log_guard);
+    code
+  }
+
+  // Runs until shutdown. The follower only checks for shutdown between blocks, so the
+  // block being loaded is either committed or, on error, its transaction is rolled back.
+  async fn follow(settings: &Settings, pool: Option<Pool>, logger: &Logger, shutdown_listener: triggered::Listener) -> i32 {
+    let mut tasks = vec!();
+    if let Some(http) = &settings.http {
+      info!(logger, "Serving metrics and health checks on {}", http.listen_addr);
+      let stall_timeout = time::Duration::from_secs(http.stall_timeout_secs);
+      let server_logger = logger.clone();
+      let server = server::serve(http.listen_addr, stall_timeout, logger.clone(), shutdown_listener.clone());
+      tasks.push(tokio::spawn(async move {
+        if let Err(e) = server.await {
+          error!(server_logger, "http server error: {}", e);
+        }
+      }));
+    }
+    let sink = match sink::from_settings(&settings, pool.clone(), &logger) {
+      Ok(s) => s,
+      Err(e) => {
+        error!(logger, "couldn't open sink: {}", e);
+        return 1
+      }
+    };
+    let mut follower = match Follower::new(&settings, sink, pool.clone(), &logger, shutdown_listener.clone()).await {
+      Ok(f) => f,
+      Err(e) => {
+        error!(logger, "couldn't start follower: {}", e);
+        return 1
+      }
+    };
     info!(logger, "Starting blockchain follower at height: {}", follower.height);
+    // filters mode always has a pool, it is only allowed with the postgres sink
+    if let (EtlMode::Filters, Some(pool)) = (settings.mode, &pool) {
+      let backfill = Backfill::new(&settings, pool.clone(), &logger, shutdown_listener.clone());
+      tasks.push(tokio::spawn(backfill.run()));
+    }
     let mut interval = time::interval(time::Duration::from_secs(10));
     loop {
       tokio::select! {
-        _ = shutdown_listener.clone() => {
-          info!(logger, "Goodbye!");
-          return
-        },
+        _ = shutdown_listener.clone() => break,
         _ = interval.tick() => {
           follower.run().await;
         }
       }
-    } 
-  } 
+    }
+    info!(logger, "stopped at height {}, waiting for background tasks", follower.height);
+    for task in tasks {
+      let _ = task.await;
+    }
+    0
+  }
+}
+
+// Resolves on the first SIGINT or SIGTERM.
+#[cfg(unix)]
+async fn shutdown_signal(logger: &Logger) {
+  use tokio::signal::unix::{signal, SignalKind};
+  let mut sigterm = signal(SignalKind::terminate()).expect("installing SIGTERM handler");
+  tokio::select! {
+    _ = tokio::signal::ctrl_c() => info!(logger, "received SIGINT, shutting down"),
+    _ = sigterm.recv() => info!(logger, "received SIGTERM, shutting down"),
+  }
 }
 
-fn start_logger(settings: &Settings) -> Logger {
-  let log_dir = &settings.log.log_dir;
-  let log_path = format!("{}/etl_lite.log", log_dir);
-  fs::create_dir_all(log_dir).unwrap();
-  let file = OpenOptions::new()
-    .create(true)
-    .write(true)
-    .truncate(true)
-    .open(log_path)
-    .expect("opening file");
-  let decorator = slog_term::PlainDecorator::new(file);
-  let drain = slog_term::FullFormat::new(decorator)
-    .use_custom_timestamp(slog_term::timestamp_local)
-    .build()
-    .fuse();
-  let async_drain = slog_async::Async::new(drain)
-    .build()
-    .fuse();
-  slog::Logger::root(async_drain, o!())
+#[cfg(not(unix))]
+async fn shutdown_signal(logger: &Logger) {
+  let _ = tokio::signal::ctrl_c().await;
+  info!(logger, "received ctrl-c, shutting down");
 }
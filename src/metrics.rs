@@ -0,0 +1,48 @@
+use lazy_static::lazy_static;
+use prometheus::{
+  register_histogram_vec, register_int_counter, register_int_counter_vec, register_int_gauge, register_gauge,
+  Encoder, Gauge, HistogramVec, IntCounter, IntCounterVec, IntGauge, TextEncoder,
+};
+use std::future::Future;
+
+lazy_static! {
+  pub static ref HEIGHT: IntGauge =
+    register_int_gauge!("etl_height", "Height of the last block committed to the database").unwrap();
+  pub static ref NODE_HEIGHT: IntGauge =
+    register_int_gauge!("etl_node_height", "Current height reported by blockchain-node").unwrap();
+  pub static ref HEIGHT_LAG: IntGauge =
+    register_int_gauge!("etl_height_lag", "Blocks the database is behind blockchain-node").unwrap();
+  pub static ref BLOCKS: IntCounter =
+    register_int_counter!("etl_blocks_total", "Blocks committed to the database").unwrap();
+  pub static ref BLOCKS_PER_SECOND: Gauge =
+    register_gauge!("etl_blocks_per_second", "Blocks committed per second during the last follower run").unwrap();
+  pub static ref TRANSACTIONS: IntCounterVec =
+    register_int_counter_vec!("etl_transactions_total", "Transactions in committed blocks, stored or not, by type", &["type"]).unwrap();
+  pub static ref REWARDS: IntCounter =
+    register_int_counter!("etl_rewards_total", "Rewards inserted into the rewards table").unwrap();
+  pub static ref RPC_DURATION: HistogramVec =
+    register_histogram_vec!("etl_rpc_duration_seconds", "Latency of blockchain-node JSON-RPC calls", &["method"]).unwrap();
+  pub static ref ERRORS: IntCounterVec =
+    register_int_counter_vec!("etl_errors_total", "Errors encountered, by kind", &["kind"]).unwrap();
+}
+
+/// Time a JSON-RPC call to the node under the given method name.
+pub async fn time_rpc<F: Future>(method: &str, call: F) -> F::Output {
+  let timer = RPC_DURATION.with_label_values(&[method]).start_timer();
+  let result = call.await;
+  timer.observe_duration();
+  result
+}
+
+pub fn error(kind: &str) {
+  ERRORS.with_label_values(&[kind]).inc();
+}
+
+/// Render every registered metric in the Prometheus text format.
+/// Returns the content type and the encoded metrics.
+pub fn encode() -> prometheus::Result<(String, Vec<u8>)> {
+  let encoder = TextEncoder::new();
+  let mut buffer = vec!();
+  encoder.encode(&prometheus::gather(), &mut buffer)?;
+  Ok((encoder.format_type().to_string(), buffer))
+}
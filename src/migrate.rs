@@ -1,5 +1,4 @@
 use crate::*;
-use tokio_postgres::NoTls;
 
 mod embedded {
     use refinery::embed_migrations;
@@ -7,12 +6,7 @@ mod embedded {
 }
 
 pub async fn run(settings: &Settings) {
-  let (mut client, connection) = tokio_postgres::connect(&settings.database_url, NoTls).await.unwrap();
-  tokio::spawn(async move {
-      if let Err(e) = connection.await {
-          eprintln!("connection error: {}", e);
-      }
-  });
+  let mut client = db::connect(settings).await.unwrap();
   let res = embedded::migrations::runner().run_async(&mut client).await.unwrap();
   println!("{:?}", res);
 }
\ No newline at end of file
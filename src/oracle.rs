@@ -0,0 +1,36 @@
+use crate::*;
+use tokio_postgres::{ Transaction as PgTransaction };
+use helium_jsonrpc::Transaction;
+use std::convert::TryFrom;
+
+// Transaction types carrying an oracle price. These are loaded in every mode.
+pub const ORACLE_TRANSACTION_TYPES: &[&str] = &[
+  "price_oracle_v1",
+];
+
+/// The oracle's public key and the price it reported, in 1/100_000_000 USD per HNT.
+pub fn price_report(transaction: &Transaction) -> Option<(&str, u64)> {
+  match transaction {
+    Transaction::PriceOracleV1(p) => Some((&p.public_key, p.price)),
+    _ => None,
+  }
+}
+
+/// Record the price reports in a block. Each entry is (transaction hash, public key, price).
+pub async fn add_prices<'a>(pgtran: &'a PgTransaction<'a>, block: u64, time: u64, prices: &[(&str, &str, u64)]) -> Result<u64> {
+  if prices.is_empty() {
+    return Ok(0);
+  }
+
+  let hashes: Vec<&str> = prices.iter().map(|p| p.0).collect();
+  let public_keys: Vec<&str> = prices.iter().map(|p| p.1).collect();
+  let values: Vec<i64> = prices.iter().map(|p| i64::try_from(p.2).unwrap()).collect();
+  let blocks: Vec<i64> = vec![i64::try_from(block).unwrap(); prices.len()];
+  let times: Vec<i64> = vec![i64::try_from(time).unwrap(); prices.len()];
+
+  pgtran.execute("INSERT INTO oracle_prices (block, transaction_hash, public_key, price, time)
+    SELECT * FROM unnest($1::bigint[], $2::text[], $3::text[], $4::bigint[], $5::bigint[])",
+    &[&blocks, &hashes, &public_keys, &values, &times])
+    .await
+    .map_err(|e| error::Error::PgError(e))
+}
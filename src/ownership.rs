@@ -0,0 +1,77 @@
+use crate::*;
+use tokio_postgres::{ Client as PgClient, Transaction as PgTransaction };
+use helium_api::IntoVec;
+use helium_jsonrpc::Transaction;
+use std::{collections::HashSet, convert::TryFrom};
+
+// Transaction types that set or change the owner of a gateway. These are loaded in every mode.
+pub const OWNERSHIP_TRANSACTION_TYPES: &[&str] = &[
+  "add_gateway_v1",
+  "transfer_hotspot_v1",
+  "transfer_hotspot_v2",
+];
+
+// transaction_hash of gateway_owners rows seeded from the Helium API
+pub const SNAPSHOT_HASH: &str = "api_snapshot";
+
+/// A Helium API client for `api_url`, where owner filters look up existing hotspots.
+pub fn api_client(settings: &Settings) -> helium_api::Client {
+  helium_api::Client::new_with_base_url(settings.api_url.clone(), "helium-etl-lite")
+}
+
+/// The gateway and its new owner, if the transaction changes ownership.
+pub fn owner_change(transaction: &Transaction) -> Option<(&str, &str)> {
+  match transaction {
+    Transaction::AddGatewayV1(g) => Some((&g.gateway, &g.owner)),
+    Transaction::TransferHotspotV1(t) => Some((&t.gateway, &t.buyer)),
+    Transaction::TransferHotspotV2(t) => Some((&t.gateway, &t.new_owner)),
+    _ => None,
+  }
+}
+
+/// Record the ownership changes in a block. Each entry is (transaction hash, gateway, owner).
+pub async fn add_owners<'a>(pgtran: &'a PgTransaction<'a>, block: u64, changes: &[(&str, &str, &str)]) -> Result<u64> {
+  if changes.is_empty() {
+    return Ok(0);
+  }
+
+  let hashes: Vec<&str> = changes.iter().map(|c| c.0).collect();
+  let gateways: Vec<&str> = changes.iter().map(|c| c.1).collect();
+  let owners: Vec<&str> = changes.iter().map(|c| c.2).collect();
+  let blocks: Vec<i64> = vec![i64::try_from(block).unwrap(); changes.len()];
+
+  pgtran.execute("INSERT INTO gateway_owners (block, transaction_hash, gateway, owner)
+    SELECT * FROM unnest($1::bigint[], $2::text[], $3::text[], $4::text[])", &[&blocks, &hashes, &gateways, &owners])
+    .await
+    .map_err(|e| error::Error::PgError(e))
+}
+
+/// Every gateway currently owned by one of `owners`.
+pub async fn owned_gateways(pgclient: &PgClient, owners: &HashSet<String>) -> Result<HashSet<String>> {
+  if owners.is_empty() {
+    return Ok(HashSet::new());
+  }
+
+  let owners: Vec<&str> = owners.iter().map(|o| o.as_str()).collect();
+  let rows = pgclient.query("SELECT DISTINCT o.gateway FROM gateway_owners o
+    WHERE o.owner = ANY($1)
+      AND NOT EXISTS (SELECT 1 FROM gateway_owners later WHERE later.gateway = o.gateway AND later.block > o.block)",
+    &[&owners]).await?;
+  Ok(rows.iter().map(|r| r.get(0)).collect())
+}
+
+/// Record the hotspots `owner` holds according to the Helium API, for gateways with no
+/// ownership change loaded yet. Ownership changes from `first_block` onwards are loaded
+/// from the chain, so the snapshot is recorded just before it: later transactions take
+/// precedence and rollbacks leave it alone. Returns the number of gateways recorded.
+pub async fn seed_owner(pgclient: &PgClient, api: &helium_api::Client, owner: &str) -> Result<u64> {
+  let hotspots = helium_api::accounts::hotspots(api, owner).into_vec().await?;
+  let gateways: Vec<&str> = hotspots.iter().map(|h| h.address.as_str()).collect();
+  pgclient.execute("INSERT INTO gateway_owners (block, transaction_hash, gateway, owner)
+    SELECT f.first_block - 1, $1, g.gateway, $2
+    FROM follower_info f, unnest($3::text[]) AS g(gateway)
+    WHERE NOT EXISTS (SELECT 1 FROM gateway_owners o WHERE o.gateway = g.gateway)",
+    &[&SNAPSHOT_HASH, &owner, &gateways])
+    .await
+    .map_err(|e| error::Error::PgError(e))
+}
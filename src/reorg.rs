@@ -0,0 +1,78 @@
+use crate::*;
+use slog::{info, Logger};
+use tokio_postgres::{ Client as PgClient, Transaction as PgTransaction };
+use std::convert::TryFrom;
+
+// Every table holding per-block data, with the column storing the block height.
+pub const BLOCK_TABLES: &[(&str, &str)] = &[
+  ("rewards", "block"),
+  ("transactions", "block"),
+  ("payments", "block"),
+  ("add_gateways", "block"),
+  ("assert_locations", "block"),
+  ("transfer_hotspots", "block"),
+  ("state_channel_closes", "block"),
+  ("poc_receipts", "block"),
+  ("transaction_actors", "block"),
+  ("gateway_owners", "block"),
+  ("oracle_prices", "block"),
+  ("blocks", "height"),
+];
+
+/// Walk back from `height` until the hash we stored for a block matches the node's.
+/// Returns the height of the last block both sides agree on. Fails rather than guess when
+/// a block has no stored hash or nothing from `first_block` up matches.
+pub async fn find_fork_point(client: &rpc::NodeClient, sink: &mut dyn sink::Sink, logger: &Logger, height: u64, first_block: u64) -> Result<u64> {
+  let mut height = height;
+  loop {
+    let stored = match sink.block_hash(height).await? {
+      Some(h) => h,
+      None => return Err(error::Error::Custom(format!(
+        "no stored hash for block {}, can't tell whether it is on the node's chain", height))),
+    };
+    let node = match client.get_block(height).await {
+      Ok(b) => b,
+      Err(e) => return Err(error::Error::Custom(format!("couldn't get block {}: {:?}", height, e))),
+    };
+    if node.hash == stored {
+      return Ok(height);
+    }
+    info!(logger, "block {} differs from node: {} != {}", height, stored, node.hash);
+    height = match height.checked_sub(1) {
+      Some(h) if h >= first_block => h,
+      _ => return Err(error::Error::Custom(format!(
+        "no block from first_block {} up matches the node", first_block))),
+    };
+  }
+}
+
+/// Delete every row loaded for blocks above `height` and move follower_info back to it.
+/// Filters picked up above `height` are moved back to it too, and backfills that got past
+/// it resume from it, so the follower and the backfill each reload their share once.
+pub async fn rollback(pgclient: &mut PgClient, logger: &Logger, height: u64) -> Result<Option<String>> {
+  let pgtran = pgclient.build_transaction().start().await?;
+  let h = i64::try_from(height).unwrap();
+  // the filters first, as the backfill locks them before writing
+  pgtran.execute("UPDATE filters SET added_height = $1 WHERE added_height > $1", &[&h]).await?;
+  pgtran.execute("UPDATE filters SET backfill_height = $1 WHERE backfill_height > $1", &[&h]).await?;
+  for (table, column) in BLOCK_TABLES {
+    let deleted = pgtran.execute(format!("DELETE FROM {} WHERE {} > $1", table, column).as_str(), &[&h]).await?;
+    info!(logger, "rolled back {} rows from {} above block {}", deleted, table, height);
+  }
+
+  let rows = pgtran.query("SELECT hash FROM blocks WHERE height = $1", &[&h]).await?;
+  let hash: Option<String> = rows.first().map(|r| r.get(0));
+  pgtran.execute("UPDATE follower_info SET height = $1, hash = $2", &[&h, &hash]).await?;
+  pgtran.commit().await?;
+
+  Ok(hash)
+}
+
+/// Delete every row loaded for a single block so it can be loaded again.
+pub async fn delete_block<'a>(pgtran: &'a PgTransaction<'a>, height: u64) -> Result<()> {
+  let h = i64::try_from(height).unwrap();
+  for (table, column) in BLOCK_TABLES {
+    pgtran.execute(format!("DELETE FROM {} WHERE {} = $1", table, column).as_str(), &[&h]).await?;
+  }
+  Ok(())
+}
@@ -1,52 +1,45 @@
 use crate::*;
-use tokio_postgres::{Transaction, Statement};
+use tokio_postgres::{Transaction, binary_copy::BinaryCopyInWriter, types::Type};
 use helium_api::models::transactions::Reward;
+use futures::pin_mut;
 use std::{convert::TryFrom};
 
-pub async fn prepare<'a>(pgtran: &'a Transaction<'a>) -> Result<Statement>{
-  let stmt = pgtran.prepare("INSERT INTO rewards (block, transaction_hash, time, account, gateway, amount, type)
-    VALUES ($1, $2, $3, $4, $5, $6, $7)").await;
-  match stmt {
-    Ok(s) => Ok(s),
-    Err(e) => Err(error::Error::PgError(e)),
+// placeholder for rewards without a gateway, or without an account for overages
+const DEFAULT_ADDRESS: &str = "1Wh4bh";
+
+/// Stream all rewards of a block into the `rewards` table with a single binary COPY.
+/// Returns the number of rows written.
+pub async fn add_rewards<'a>(pgtran: &'a Transaction<'a>,
+  block: u64,
+  time: u64,
+  hash: String,
+  rewards: &[&Reward]) -> Result<u64> {
+  if rewards.is_empty() {
+    return Ok(0);
   }
-}
 
-pub async fn add_reward<'a>(pgtran: &'a Transaction<'a>,
-  block: u64, 
-  time: u64, 
-  hash: String, 
-  reward: &Reward) -> Result<Vec<tokio_postgres::Row>> {
-  let stmt = prepare(&pgtran).await.unwrap();
-  let gateway: &String;
-  let default: &String = &String::from("1Wh4bh");
+  let sink = pgtran.copy_in("COPY rewards (block, transaction_hash, time, account, gateway, amount, type)
+    FROM STDIN BINARY").await?;
+  let writer = BinaryCopyInWriter::new(sink, &[Type::INT8, Type::TEXT, Type::INT8, Type::TEXT, Type::TEXT, Type::INT8, Type::TEXT]);
+  pin_mut!(writer);
+
+  let block = i64::try_from(block).unwrap();
+  let time = i64::try_from(time).unwrap();
+  for reward in rewards {
+    let (account, gateway, amount) = reward_columns(reward)?;
+    writer.as_mut().write(&[&block, &hash, &time, &account, &gateway, &amount, &reward.r#type]).await?;
+  }
 
-  gateway = match &reward.gateway {
-    Some(g) => g,
-    None => default,
-  };
+  writer.finish().await.map_err(|e| error::Error::PgError(e))
+}
 
+pub(crate) fn reward_columns(reward: &Reward) -> Result<(&str, &str, i64)> {
+  let gateway = reward.gateway.as_deref().unwrap_or(DEFAULT_ADDRESS);
   // for overages
-  let account = match &reward.account {
-    Some(a) => a,
-    None => default,
-  };
+  let account = reward.account.as_deref().unwrap_or(DEFAULT_ADDRESS);
 
   let amount = Into::<u64>::into(reward.amount);
   let amount = i64::try_from(amount).map_err(|e| Error::Custom(format!("failed to convert amount {} to u64: {}", amount, e.to_string())))?;
 
-  match pgtran.query(&stmt, &[&i64::try_from(block).unwrap(),
-      &hash, 
-      &i64::try_from(time).unwrap(), 
-      &account, 
-      &gateway,
-      &amount,
-      &reward.r#type]).await {
-    Ok(v) => Ok(v),
-    Err(e) => {
-      println!("{}", e);
-      Err(error::Error::PgError(e))
-    },
-  }   
+  Ok((account, gateway, amount))
 }
-
@@ -0,0 +1,81 @@
+use crate::*;
+use crate::settings::Retry;
+use helium_jsonrpc::{ Client, blocks, blocks::BlockRaw, transactions, Transaction };
+use rand::Rng;
+use std::{future::Future, time::Duration};
+use tokio::time;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorKind {
+  // worth retrying: timeouts, refused connections, 5xx responses
+  Transient,
+  // retrying won't help: the node answered with an error, e.g. txn not found
+  Permanent,
+}
+
+pub fn classify(e: &helium_jsonrpc::Error) -> ErrorKind {
+  match e {
+    helium_jsonrpc::Error::Request(e)
+      if e.is_timeout() || e.is_connect() || e.status().map_or(false, |s| s.is_server_error()) => {
+      ErrorKind::Transient
+    },
+    _ => ErrorKind::Permanent,
+  }
+}
+
+/// A blockchain-node client that retries transient failures with exponential backoff
+/// and jitter. Permanent failures are returned straight away.
+pub struct NodeClient {
+  client: Client,
+  retry: Retry,
+}
+
+impl NodeClient {
+  pub fn new(settings: &Settings) -> Self {
+    Self {
+      client: Client::new_with_base_url(settings.node_addr.to_string()),
+      retry: settings.retry,
+    }
+  }
+
+  pub async fn height(&self) -> std::result::Result<u64, helium_jsonrpc::Error> {
+    self.with_retry("block_height", || blocks::height(&self.client)).await
+  }
+
+  pub async fn get_block(&self, height: u64) -> std::result::Result<BlockRaw, helium_jsonrpc::Error> {
+    self.with_retry("block_get", || blocks::get_raw(&self.client, &height)).await
+  }
+
+  pub async fn get_transaction(&self, hash: &str) -> std::result::Result<Transaction, helium_jsonrpc::Error> {
+    self.with_retry("transaction_get", || transactions::get(&self.client, hash)).await
+  }
+
+  async fn with_retry<T, F, Fut>(&self, method: &str, mut call: F) -> std::result::Result<T, helium_jsonrpc::Error>
+  where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = std::result::Result<T, helium_jsonrpc::Error>>,
+  {
+    let mut attempt = 1;
+    loop {
+      match metrics::time_rpc(method, call()).await {
+        Ok(v) => return Ok(v),
+        Err(e) if attempt < self.retry.attempts && classify(&e) == ErrorKind::Transient => {
+          metrics::error("rpc_retry");
+          time::sleep(backoff(&self.retry, attempt)).await;
+          attempt += 1;
+        },
+        Err(e) => {
+          metrics::error("rpc");
+          return Err(e);
+        },
+      }
+    }
+  }
+}
+
+// Exponential backoff capped at max_delay_ms, with jitter over the upper half of the delay.
+fn backoff(retry: &Retry, attempt: u32) -> Duration {
+  let exp = retry.base_delay_ms.saturating_mul(1u64 << (attempt - 1).min(32));
+  let delay = exp.min(retry.max_delay_ms);
+  Duration::from_millis(rand::thread_rng().gen_range(delay / 2..=delay))
+}
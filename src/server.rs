@@ -0,0 +1,57 @@
+use crate::*;
+use hyper::{
+  header::CONTENT_TYPE,
+  service::{make_service_fn, service_fn},
+  Body, Method, Request, Response, Server, StatusCode,
+};
+use slog::{error, Logger};
+use std::{convert::Infallible, net::SocketAddr, time::Duration};
+
+/// Serve `/metrics`, `/healthz` and `/ready` until shutdown is triggered. The follower is
+/// reported unhealthy when it hasn't advanced for `stall_timeout`.
+pub async fn serve(addr: SocketAddr, stall_timeout: Duration, logger: Logger, shutdown: triggered::Listener) -> Result<()> {
+  let make_svc = make_service_fn(move |_conn| {
+    let logger = logger.clone();
+    async move {
+      Ok::<_, Infallible>(service_fn(move |req| handle(req, stall_timeout, logger.clone())))
+    }
+  });
+  Server::try_bind(&addr)?
+    .serve(make_svc)
+    .with_graceful_shutdown(shutdown)
+    .await
+    .map_err(|e| error::Error::HttpError(e))
+}
+
+async fn handle(req: Request<Body>, stall_timeout: Duration, logger: Logger) -> std::result::Result<Response<Body>, Infallible> {
+  let response = match (req.method(), req.uri().path()) {
+    (&Method::GET, "/metrics") => match metrics::encode() {
+      Ok((content_type, body)) => Response::builder()
+        .header(CONTENT_TYPE, content_type)
+        .body(Body::from(body)),
+      Err(e) => {
+        error!(logger, "couldn't encode metrics: {}", e);
+        text(StatusCode::INTERNAL_SERVER_ERROR, "couldn't encode metrics".to_string())
+      },
+    },
+    (&Method::GET, "/healthz") => match health::is_healthy(stall_timeout) {
+      true => text(StatusCode::OK, "ok".to_string()),
+      false => text(StatusCode::SERVICE_UNAVAILABLE, "follower has not advanced".to_string()),
+    },
+    (&Method::GET, "/ready") => match health::readiness(stall_timeout) {
+      Ok(_) => text(StatusCode::OK, "ok".to_string()),
+      Err(failing) => text(StatusCode::SERVICE_UNAVAILABLE, failing.join("\n")),
+    },
+    _ => Response::builder()
+      .status(StatusCode::NOT_FOUND)
+      .body(Body::empty()),
+  };
+  Ok(response.unwrap())
+}
+
+fn text(status: StatusCode, body: String) -> http::Result<Response<Body>> {
+  Response::builder()
+    .status(status)
+    .header(CONTENT_TYPE, "text/plain")
+    .body(Body::from(body))
+}
@@ -1,7 +1,7 @@
 use crate::*;
-use config::{Config, File};
+use config::{Config, Environment, File};
 use serde::{de, Deserialize, Deserializer};
-use std::path::PathBuf;
+use std::{env, fmt, net::SocketAddr, path::Path};
 use http::uri::Uri;
 
 #[derive(Debug, Deserialize, Clone, Copy)]
@@ -14,6 +14,105 @@ pub enum EtlMode {
 #[derive(Debug, Deserialize)]
 pub struct Log {
   pub log_dir: String,
+
+  #[serde(default = "default_log_level", deserialize_with = "deserialize_log_level")]
+  pub level: slog::Level,
+
+  #[serde(default = "default_log_format")]
+  pub format: LogFormat,
+
+  #[serde(default = "default_log_output")]
+  pub output: LogOutput,
+
+  #[serde(default = "default_log_rotation")]
+  pub rotation: LogRotation,
+
+  // only used with `rotation = "size"`
+  #[serde(default = "default_log_max_size_mb")]
+  pub max_size_mb: usize,
+
+  #[serde(default = "default_log_max_files")]
+  pub max_files: usize,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+  Text,
+  Json,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum LogOutput {
+  File,
+  Stdout,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum LogRotation {
+  Size,
+  Hourly,
+  Daily,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Http {
+  pub listen_addr: SocketAddr,
+
+  #[serde(default = "default_stall_timeout_secs")]
+  pub stall_timeout_secs: u64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Tls {
+  // PEM file with the CA certificate(s) the server's certificate is checked against,
+  // in addition to the system roots
+  pub ca_cert: Option<String>,
+  // PEM certificate and PKCS#8 key, for servers that require client certificates
+  pub client_cert: Option<String>,
+  pub client_key: Option<String>,
+}
+
+/// Where blocks are written. Filters mode, `migrate`, `verify`, `repair` and `load`
+/// need Postgres.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum SinkSettings {
+  Postgres,
+  // a directory for etl_lite.ndjson and state.json
+  Ndjson { path: String },
+  // a SQLite database file
+  Sqlite { path: String },
+}
+
+impl Default for SinkSettings {
+  fn default() -> Self {
+    SinkSettings::Postgres
+  }
+}
+
+#[derive(Debug, Deserialize, Clone, Copy)]
+pub struct Retry {
+  #[serde(default = "default_retry_attempts")]
+  pub attempts: u32,
+
+  #[serde(default = "default_retry_base_delay_ms")]
+  pub base_delay_ms: u64,
+
+  #[serde(default = "default_retry_max_delay_ms")]
+  pub max_delay_ms: u64,
+}
+
+impl Default for Retry {
+  fn default() -> Self {
+    Retry {
+      attempts: default_retry_attempts(),
+      base_delay_ms: default_retry_base_delay_ms(),
+      max_delay_ms: default_retry_max_delay_ms(),
+    }
+  }
 }
 
 #[derive(Debug, Deserialize)]
@@ -37,6 +136,12 @@ pub struct Settings {
   #[serde(deserialize_with = "deserialize_uri")]  
   pub node_addr: Uri,
 
+  // Helium API, where owner filters look up the hotspots an account already holds
+  #[serde(default = "default_api_url")]
+  pub api_url: String,
+
+  // only needed with the postgres sink
+  #[serde(default)]
   pub database_url: String,
 
   #[serde(deserialize_with = "deserialize_etl_mode")]
@@ -45,15 +150,173 @@ pub struct Settings {
   #[serde(deserialize_with = "deserialize_backfill")] 
   pub backfill: bool,
 
+  #[serde(default = "default_prefetch")]
+  pub prefetch: usize,
+
+  #[serde(default = "default_pool_size")]
+  pub pool_size: usize,
+
+  #[serde(default = "default_filter_refresh_secs")]
+  pub filter_refresh_secs: u64,
+
+  #[serde(default)]
+  pub http: Option<Http>,
+
+  #[serde(default)]
+  pub retry: Retry,
+
+  #[serde(default)]
+  pub tls: Option<Tls>,
+
+  #[serde(default)]
+  pub sink: SinkSettings,
+
 }
 
 impl Settings {
-  pub fn new() -> Result<Self> {
+  /// Load settings from `path`, overridden by any `ETL_`-prefixed environment variables.
+  /// Nested settings use a double underscore, e.g. `ETL_RETRY__ATTEMPTS`. `ETL_LOG_DIR`
+  /// is accepted as a shorthand for `ETL_LOG__LOG_DIR`.
+  pub fn new(path: &Path) -> Result<Self> {
     let mut con = Config::new();
-    let path = PathBuf::from("config/settings.toml");
-    con.merge(File::with_name(path.to_str().expect("file name")))?;
-    con.try_into().map_err(|e| e.into())
+    con.merge(File::from(path))?;
+    con.merge(Environment::with_prefix("ETL").separator("__"))?;
+    if let Ok(log_dir) = env::var("ETL_LOG_DIR") {
+      con.set("log.log_dir", log_dir)?;
+    }
+    let settings: Settings = con.try_into()?;
+    if let (EtlMode::Filters, false) = (settings.mode, settings.uses_postgres()) {
+      return Err(Error::custom("filters mode needs the postgres sink"));
+    }
+    Ok(settings)
+  }
+
+  pub fn uses_postgres(&self) -> bool {
+    matches!(self.sink, SinkSettings::Postgres)
   }
+
+  /// `database_url` with its password replaced, safe to print or log.
+  pub fn redacted_database_url(&self) -> String {
+    match url::Url::parse(&self.database_url) {
+      Ok(mut url) => {
+        if url.password().is_some() {
+          let _ = url.set_password(Some("REDACTED"));
+        }
+        url.to_string()
+      },
+      Err(_) => "REDACTED".to_string(),
+    }
+  }
+}
+
+impl fmt::Display for EtlMode {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      EtlMode::Rewards => write!(f, "rewards"),
+      EtlMode::Full => write!(f, "full"),
+      EtlMode::Filters => write!(f, "filters"),
+    }
+  }
+}
+
+// Prints the effective settings in settings.toml form, with secrets redacted.
+impl fmt::Display for Settings {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    writeln!(f, "node_addr = \"{}\"", self.node_addr)?;
+    writeln!(f, "api_url = \"{}\"", self.api_url)?;
+    writeln!(f, "database_url = \"{}\"", self.redacted_database_url())?;
+    writeln!(f, "mode = \"{}\"", self.mode)?;
+    writeln!(f, "backfill = \"{}\"", self.backfill)?;
+    writeln!(f, "prefetch = {}", self.prefetch)?;
+    writeln!(f, "pool_size = {}", self.pool_size)?;
+    writeln!(f, "filter_refresh_secs = {}", self.filter_refresh_secs)?;
+    if let Some(http) = &self.http {
+      writeln!(f, "\n[http]")?;
+      writeln!(f, "listen_addr = \"{}\"", http.listen_addr)?;
+      writeln!(f, "stall_timeout_secs = {}", http.stall_timeout_secs)?;
+    }
+    writeln!(f, "\n[retry]")?;
+    writeln!(f, "attempts = {}", self.retry.attempts)?;
+    writeln!(f, "base_delay_ms = {}", self.retry.base_delay_ms)?;
+    writeln!(f, "max_delay_ms = {}", self.retry.max_delay_ms)?;
+    match &self.sink {
+      SinkSettings::Postgres => writeln!(f, "\n[sink]\ntype = \"postgres\"")?,
+      SinkSettings::Ndjson { path } => writeln!(f, "\n[sink]\ntype = \"ndjson\"\npath = \"{}\"", path)?,
+      SinkSettings::Sqlite { path } => writeln!(f, "\n[sink]\ntype = \"sqlite\"\npath = \"{}\"", path)?,
+    }
+    if let Some(tls) = &self.tls {
+      writeln!(f, "\n[tls]")?;
+      for (name, value) in &[("ca_cert", &tls.ca_cert), ("client_cert", &tls.client_cert), ("client_key", &tls.client_key)] {
+        if let Some(v) = value {
+          writeln!(f, "{} = \"{}\"", name, v)?;
+        }
+      }
+    }
+    writeln!(f, "\n[log]")?;
+    writeln!(f, "log_dir = \"{}\"", self.log.log_dir)?;
+    writeln!(f, "level = \"{}\"", self.log.level.as_str().to_lowercase())?;
+    writeln!(f, "format = \"{}\"", format!("{:?}", self.log.format).to_lowercase())?;
+    writeln!(f, "output = \"{}\"", format!("{:?}", self.log.output).to_lowercase())?;
+    writeln!(f, "rotation = \"{}\"", format!("{:?}", self.log.rotation).to_lowercase())?;
+    writeln!(f, "max_size_mb = {}", self.log.max_size_mb)?;
+    write!(f, "max_files = {}", self.log.max_files)
+  }
+}
+
+fn default_log_level() -> slog::Level {
+  slog::Level::Info
+}
+
+fn default_log_format() -> LogFormat {
+  LogFormat::Text
+}
+
+fn default_log_output() -> LogOutput {
+  LogOutput::File
+}
+
+fn default_log_rotation() -> LogRotation {
+  LogRotation::Daily
+}
+
+fn default_log_max_size_mb() -> usize {
+  100
+}
+
+fn default_log_max_files() -> usize {
+  10
+}
+
+fn default_api_url() -> String {
+  helium_api::DEFAULT_BASE_URL.to_string()
+}
+
+fn default_prefetch() -> usize {
+  8
+}
+
+fn default_pool_size() -> usize {
+  4
+}
+
+fn default_filter_refresh_secs() -> u64 {
+  10
+}
+
+fn default_stall_timeout_secs() -> u64 {
+  600
+}
+
+fn default_retry_attempts() -> u32 {
+  5
+}
+
+fn default_retry_base_delay_ms() -> u64 {
+  250
+}
+
+fn default_retry_max_delay_ms() -> u64 {
+  30_000
 }
 
 fn deserialize_uri<'de, D>(d: D) -> std::result::Result<Uri, D::Error>
@@ -85,6 +348,17 @@ where
     Ok(mode)
 }
 
+fn deserialize_log_level<'de, D>(d: D) -> std::result::Result<slog::Level, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let level = String::deserialize(d)?;
+    match level.parse() {
+        Ok(l) => Ok(l),
+        Err(_) => Err(de::Error::custom(format!("unsupported log level: \"{}\"", level))),
+    }
+}
+
 fn deserialize_backfill<'de, D>(d: D) -> std::result::Result<bool, D::Error>
 where
     D: Deserializer<'de>,
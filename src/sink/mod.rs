@@ -0,0 +1,76 @@
+use crate::*;
+use crate::block_processor::FetchedTransaction;
+use crate::settings::SinkSettings;
+use async_trait::async_trait;
+use deadpool_postgres::Pool;
+use helium_api::models::transactions::Reward;
+use helium_jsonrpc::blocks::BlockRaw;
+use slog::Logger;
+
+pub mod postgres;
+pub mod ndjson;
+pub mod sqlite;
+
+pub use self::{ndjson::NdjsonSink, postgres::PostgresSink, sqlite::SqliteSink};
+
+/// How far the follower has got: the last block written and the block it started from.
+#[derive(Debug, Clone)]
+pub struct Position {
+  pub height: u64,
+  pub hash: Option<String>,
+  pub first_block: u64,
+}
+
+/// Everything selected from a block for the current mode and filters.
+pub struct Block<'a> {
+  pub block: &'a BlockRaw,
+  pub rewards: Vec<&'a Reward>,
+  // transactions to store, with their actors
+  pub transactions: Vec<&'a FetchedTransaction>,
+  pub actors: Vec<(&'a str, &'a str, actor::Role)>,
+  // (transaction hash, gateway, owner) for every ownership change in the block
+  pub owners: Vec<(&'a str, &'a str, &'a str)>,
+  // (transaction hash, oracle public key, price) for every price report in the block
+  pub prices: Vec<(&'a str, &'a str, u64)>,
+}
+
+/// Where the follower writes blocks to, along with its own position so that the two
+/// can't drift apart.
+#[async_trait]
+pub trait Sink: Send {
+  /// The current position, or None if nothing has been written yet.
+  async fn position(&mut self) -> Result<Option<Position>>;
+  /// Start a new sink at `first_block`.
+  async fn start(&mut self, first_block: u64) -> Result<()>;
+  /// Write a block and advance the position to it, all or nothing.
+  async fn write_block(&mut self, block: &Block<'_>) -> Result<()>;
+  /// The hash stored for `height`, if it is known.
+  async fn block_hash(&mut self, height: u64) -> Result<Option<String>>;
+  /// Drop everything written above `height` and move the position back to it.
+  /// Returns the hash stored for `height`.
+  async fn rollback(&mut self, height: u64) -> Result<Option<String>>;
+}
+
+/// The sink selected by the `[sink]` settings. `pool` must be set for the Postgres sink.
+pub fn from_settings(settings: &Settings, pool: Option<Pool>, logger: &Logger) -> Result<Box<dyn Sink>> {
+  match &settings.sink {
+    SinkSettings::Postgres => match pool {
+      Some(p) => Ok(Box::new(PostgresSink::new(p, logger))),
+      None => Err(error::Error::custom("the postgres sink needs a connection pool")),
+    },
+    SinkSettings::Ndjson { path } => Ok(Box::new(NdjsonSink::open(path)?)),
+    SinkSettings::Sqlite { path } => Ok(Box::new(SqliteSink::open(path)?)),
+  }
+}
+
+/// Run blocking file or database I/O on tokio's blocking thread pool.
+pub(crate) async fn blocking<T, F>(f: F) -> Result<T>
+where
+  T: Send + 'static,
+  F: FnOnce() -> Result<T> + Send + 'static,
+{
+  match tokio::task::spawn_blocking(f).await {
+    Ok(r) => r,
+    Err(e) => Err(error::Error::Custom(format!("blocking sink task failed: {}", e))),
+  }
+}
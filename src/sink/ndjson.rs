@@ -0,0 +1,158 @@
+use crate::*;
+use crate::sink::{self, Block, Position, Sink};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::{collections::BTreeMap, fs::{self, File, OpenOptions}, io::Write, path::PathBuf, sync::{Arc, Mutex}};
+
+// How many recent block hashes are kept in the state file to find fork points with.
+const KEPT_HASHES: usize = 1000;
+
+/// Appends one JSON object per line to `etl_lite.ndjson` in the sink directory, each
+/// tagged with its `kind`: block, reward, transaction, actor, gateway_owner, oracle_price
+/// or rollback.
+/// The position is kept in `state.json` next to it.
+///
+/// A block's lines are flushed to disk before the state is replaced, so a crash in
+/// between writes that block again on restart. Readers should treat lines as
+/// idempotent and, on a `rollback` line, drop everything above its `height`.
+///
+/// Writes are fsynced, so they run on tokio's blocking thread pool.
+pub struct NdjsonSink {
+  out: Arc<Mutex<File>>,
+  state_path: PathBuf,
+  state: Option<State>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct State {
+  height: u64,
+  hash: Option<String>,
+  first_block: u64,
+  hashes: BTreeMap<u64, String>,
+}
+
+impl NdjsonSink {
+  pub fn open(dir: &str) -> Result<Self> {
+    fs::create_dir_all(dir)?;
+    let dir = PathBuf::from(dir);
+    let out = OpenOptions::new()
+      .create(true)
+      .append(true)
+      .open(dir.join("etl_lite.ndjson"))?;
+    let state_path = dir.join("state.json");
+    let state = match fs::read(&state_path) {
+      Ok(contents) => Some(serde_json::from_slice(&contents)?),
+      Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+      Err(e) => return Err(e.into()),
+    };
+    Ok(Self { out: Arc::new(Mutex::new(out)), state_path, state })
+  }
+
+  async fn append(&self, lines: Vec<serde_json::Value>) -> Result<()> {
+    let mut buf = vec!();
+    for line in lines {
+      serde_json::to_writer(&mut buf, &line)?;
+      buf.push(b'\n');
+    }
+    let out = self.out.clone();
+    sink::blocking(move || {
+      let mut out = out.lock().unwrap();
+      out.write_all(&buf)?;
+      out.sync_data()?;
+      Ok(())
+    }).await
+  }
+
+  // replaced atomically so a crash never leaves a partial state file
+  async fn save_state(&self) -> Result<()> {
+    let contents = serde_json::to_vec(&self.state)?;
+    let path = self.state_path.clone();
+    sink::blocking(move || {
+      let tmp = path.with_extension("json.tmp");
+      fs::write(&tmp, contents)?;
+      fs::rename(&tmp, &path)?;
+      Ok(())
+    }).await
+  }
+
+  fn state(&mut self) -> Result<&mut State> {
+    self.state.as_mut().ok_or_else(|| error::Error::custom("ndjson sink has not been started"))
+  }
+}
+
+#[async_trait]
+impl Sink for NdjsonSink {
+  async fn position(&mut self) -> Result<Option<Position>> {
+    Ok(self.state.as_ref().map(|s| Position {
+      height: s.height,
+      hash: s.hash.clone(),
+      first_block: s.first_block,
+    }))
+  }
+
+  async fn start(&mut self, first_block: u64) -> Result<()> {
+    self.state = Some(State {
+      height: first_block - 1,
+      hash: None,
+      first_block,
+      hashes: BTreeMap::new(),
+    });
+    self.save_state().await
+  }
+
+  async fn write_block(&mut self, block: &Block<'_>) -> Result<()> {
+    let raw = block.block;
+    let mut lines = vec!();
+    for reward in &block.rewards {
+      let (account, gateway, amount) = reward::reward_columns(reward)?;
+      lines.push(json!({"kind": "reward", "block": raw.height, "transaction_hash": raw.hash, "time": raw.time,
+        "account": account, "gateway": gateway, "amount": amount, "type": reward.r#type}));
+    }
+    for txn in &block.transactions {
+      lines.push(json!({"kind": "transaction", "block": raw.height, "hash": txn.hash, "type": txn.r#type,
+        "fields": txn.transaction}));
+    }
+    for (hash, actor, role) in &block.actors {
+      lines.push(json!({"kind": "actor", "block": raw.height, "transaction_hash": hash, "actor": actor,
+        "role": role.as_str()}));
+    }
+    for (hash, gateway, owner) in &block.owners {
+      lines.push(json!({"kind": "gateway_owner", "block": raw.height, "transaction_hash": hash,
+        "gateway": gateway, "owner": owner}));
+    }
+    for (hash, public_key, price) in &block.prices {
+      lines.push(json!({"kind": "oracle_price", "block": raw.height, "transaction_hash": hash,
+        "public_key": public_key, "price": price, "time": raw.time}));
+    }
+    lines.push(json!({"kind": "block", "height": raw.height, "hash": raw.hash, "prev_hash": raw.prev_hash,
+      "time": raw.time, "transaction_count": raw.transactions.len(), "reward_count": block.rewards.len()}));
+    self.append(lines).await?;
+
+    let state = self.state()?;
+    state.height = raw.height;
+    state.hash = Some(raw.hash.to_string());
+    state.hashes.insert(raw.height, raw.hash.to_string());
+    while state.hashes.len() > KEPT_HASHES {
+      let oldest = *state.hashes.keys().next().unwrap();
+      state.hashes.remove(&oldest);
+    }
+    self.save_state().await
+  }
+
+  async fn block_hash(&mut self, height: u64) -> Result<Option<String>> {
+    Ok(self.state()?.hashes.get(&height).cloned())
+  }
+
+  async fn rollback(&mut self, height: u64) -> Result<Option<String>> {
+    self.append(vec![json!({"kind": "rollback", "height": height})]).await?;
+    let state = self.state()?;
+    // keeps everything up to and including height
+    state.hashes.split_off(&(height + 1));
+    state.height = height;
+    state.hash = state.hashes.get(&height).cloned();
+    let hash = state.hash.clone();
+    self.save_state().await?;
+    Ok(hash)
+  }
+}
@@ -0,0 +1,109 @@
+use crate::*;
+use crate::sink::{Block, Position, Sink};
+use async_trait::async_trait;
+use deadpool_postgres::Pool;
+use slog::Logger;
+use tokio_postgres::{ Client as PgClient, Transaction as PgTransaction };
+use std::convert::TryFrom;
+
+/// Writes into the Postgres schema managed by `migrate`. Every block is written in one
+/// database transaction together with `follower_info`.
+pub struct PostgresSink {
+  pool: Pool,
+  logger: Logger,
+}
+
+impl PostgresSink {
+  pub fn new(pool: Pool, logger: &Logger) -> Self {
+    Self {
+      pool,
+      logger: logger.clone(),
+    }
+  }
+}
+
+#[async_trait]
+impl Sink for PostgresSink {
+  async fn position(&mut self) -> Result<Option<Position>> {
+    let pgclient = self.pool.get().await?;
+    let rows = pgclient.query("SELECT height, first_block, hash FROM follower_info", &[]).await?;
+    Ok(rows.first().map(|r| Position {
+      height: u64::try_from(r.get::<_, i64>(0)).unwrap(),
+      first_block: u64::try_from(r.get::<_, i64>(1)).unwrap(),
+      hash: r.get(2),
+    }))
+  }
+
+  async fn start(&mut self, first_block: u64) -> Result<()> {
+    let pgclient = self.pool.get().await?;
+    // nothing is loaded yet, the first block to load is first_block
+    pgclient.execute("INSERT INTO follower_info (height, first_block, blocks_from) VALUES ($1, $2, $2)",
+      &[&i64::try_from(first_block - 1).unwrap(), &i64::try_from(first_block).unwrap()]).await?;
+    Ok(())
+  }
+
+  async fn write_block(&mut self, block: &Block<'_>) -> Result<()> {
+    let mut conn = self.pool.get().await?;
+    let pgclient: &mut PgClient = &mut conn;
+    let pgtran = pgclient.transaction().await?;
+    load(&pgtran, block).await?;
+    pgtran.execute("UPDATE follower_info SET height = $1, hash = $2",
+      &[&i64::try_from(block.block.height).unwrap(), &block.block.hash]).await?;
+    pgtran.commit().await.map_err(|e| error::Error::PgError(e))
+  }
+
+  async fn block_hash(&mut self, height: u64) -> Result<Option<String>> {
+    let pgclient = self.pool.get().await?;
+    block::get_hash(&pgclient, height).await
+  }
+
+  async fn rollback(&mut self, height: u64) -> Result<Option<String>> {
+    let mut pgclient = self.pool.get().await?;
+    reorg::rollback(&mut pgclient, &self.logger, height).await
+  }
+}
+
+/// Insert a block and everything selected from it. follower_info is left untouched.
+pub async fn load<'a>(pgtran: &'a PgTransaction<'a>, block: &Block<'_>) -> Result<()> {
+  let raw = block.block;
+  let reward_count = match reward::add_rewards(pgtran, raw.height, raw.time, raw.hash.to_string(), &block.rewards).await {
+    Ok(n) => n,
+    Err(e) => {
+      return Err(Error::Custom(format!("Error adding rewards {:?}", e)));
+    },
+  };
+  for txn in &block.transactions {
+    match transaction::add_transaction(pgtran, raw.height, txn.hash.to_string(), txn.r#type.as_str(), &txn.transaction).await {
+      Ok(_) => (),
+      Err(e) => {
+        return Err(Error::Custom(format!("Error adding transaction: {}. {:?}", txn.hash, e)));
+      },
+    }
+    match typed_transaction::add_typed_transaction(pgtran, raw.height, &txn.hash, txn.r#type.as_str(), &txn.transaction).await {
+      Ok(_) => (),
+      Err(e) => {
+        return Err(Error::Custom(format!("Error adding typed transaction: {}. {:?}", txn.hash, e)));
+      },
+    }
+  }
+  match actor::add_actors(pgtran, raw.height, &block.actors).await {
+    Ok(_) => (),
+    Err(e) => {
+      return Err(Error::Custom(format!("Error adding transaction actors in block {}. {:?}", raw.height, e)));
+    },
+  }
+  match ownership::add_owners(pgtran, raw.height, &block.owners).await {
+    Ok(_) => (),
+    Err(e) => {
+      return Err(Error::Custom(format!("Error adding gateway owners in block {}. {:?}", raw.height, e)));
+    },
+  }
+  match oracle::add_prices(pgtran, raw.height, raw.time, &block.prices).await {
+    Ok(_) => (),
+    Err(e) => {
+      return Err(Error::Custom(format!("Error adding oracle prices in block {}. {:?}", raw.height, e)));
+    },
+  }
+  block::add_block(pgtran, raw, reward_count).await?;
+  Ok(())
+}
@@ -0,0 +1,240 @@
+use crate::*;
+use crate::sink::{self, Block, Position, Sink};
+use async_trait::async_trait;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::{convert::TryFrom, sync::{Arc, Mutex}};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS follower_info (
+  height INTEGER NOT NULL,
+  first_block INTEGER NOT NULL,
+  hash TEXT
+);
+CREATE TABLE IF NOT EXISTS blocks (
+  height INTEGER PRIMARY KEY,
+  hash TEXT NOT NULL,
+  prev_hash TEXT,
+  time INTEGER,
+  transaction_count INTEGER,
+  reward_count INTEGER
+);
+CREATE INDEX IF NOT EXISTS blocks_time_idx ON blocks (time);
+CREATE TABLE IF NOT EXISTS rewards (
+  block INTEGER NOT NULL,
+  transaction_hash TEXT NOT NULL,
+  time INTEGER NOT NULL,
+  account TEXT NOT NULL,
+  gateway TEXT NOT NULL,
+  amount INTEGER NOT NULL,
+  type TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS rewards_block_idx ON rewards (block);
+CREATE INDEX IF NOT EXISTS rewards_account_idx ON rewards (account);
+CREATE INDEX IF NOT EXISTS rewards_gateway_idx ON rewards (gateway);
+CREATE TABLE IF NOT EXISTS transactions (
+  block INTEGER NOT NULL,
+  hash TEXT PRIMARY KEY,
+  type TEXT NOT NULL,
+  fields TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS transactions_block_idx ON transactions (block);
+CREATE TABLE IF NOT EXISTS transaction_actors (
+  actor TEXT NOT NULL,
+  actor_role TEXT NOT NULL,
+  transaction_hash TEXT NOT NULL,
+  block INTEGER NOT NULL,
+  PRIMARY KEY (actor, actor_role, transaction_hash)
+);
+CREATE INDEX IF NOT EXISTS transaction_actors_block_idx ON transaction_actors (block);
+CREATE TABLE IF NOT EXISTS gateway_owners (
+  block INTEGER NOT NULL,
+  transaction_hash TEXT NOT NULL,
+  gateway TEXT NOT NULL,
+  owner TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS gateway_owners_gateway_block_idx ON gateway_owners (gateway, block);
+CREATE TABLE IF NOT EXISTS oracle_prices (
+  block INTEGER NOT NULL,
+  transaction_hash TEXT NOT NULL,
+  public_key TEXT NOT NULL,
+  price INTEGER NOT NULL,
+  time INTEGER
+);
+CREATE INDEX IF NOT EXISTS oracle_prices_block_idx ON oracle_prices (block);
+";
+
+// Tables holding per-block data, with the column storing the block height.
+const BLOCK_TABLES: &[(&str, &str)] = &[
+  ("rewards", "block"),
+  ("transactions", "block"),
+  ("transaction_actors", "block"),
+  ("gateway_owners", "block"),
+  ("oracle_prices", "block"),
+  ("blocks", "height"),
+];
+
+/// Writes into a SQLite database file with the same core tables as Postgres (rewards,
+/// transactions as JSON text, blocks, transaction_actors, gateway_owners, oracle_prices
+/// and follower_info). The schema is created on open. The typed transaction tables are
+/// Postgres only.
+///
+/// rusqlite is synchronous, so every statement runs on tokio's blocking thread pool with
+/// the connection behind a mutex, and blocks are copied into owned rows before writing.
+pub struct SqliteSink {
+  conn: Arc<Mutex<Connection>>,
+}
+
+// A block's rows, owned so they can be moved onto a blocking thread.
+struct Rows {
+  height: i64,
+  hash: String,
+  prev_hash: String,
+  time: i64,
+  transaction_count: i64,
+  // (account, gateway, amount, type)
+  rewards: Vec<(String, String, i64, String)>,
+  // (hash, type, fields as JSON)
+  transactions: Vec<(String, String, String)>,
+  // (transaction hash, actor, role)
+  actors: Vec<(String, String, &'static str)>,
+  // (transaction hash, gateway, owner)
+  owners: Vec<(String, String, String)>,
+  // (transaction hash, oracle public key, price)
+  prices: Vec<(String, String, i64)>,
+}
+
+impl Rows {
+  fn new(block: &Block<'_>) -> Result<Self> {
+    let raw = block.block;
+    let mut rewards = Vec::with_capacity(block.rewards.len());
+    for reward in &block.rewards {
+      let (account, gateway, amount) = reward::reward_columns(reward)?;
+      rewards.push((account.to_string(), gateway.to_string(), amount, reward.r#type.clone()));
+    }
+    let mut transactions = Vec::with_capacity(block.transactions.len());
+    for txn in &block.transactions {
+      transactions.push((txn.hash.clone(), txn.r#type.clone(), serde_json::to_string(&txn.transaction)?));
+    }
+    Ok(Self {
+      height: to_i64(raw.height),
+      hash: raw.hash.clone(),
+      prev_hash: raw.prev_hash.clone(),
+      time: to_i64(raw.time),
+      transaction_count: to_i64(raw.transactions.len() as u64),
+      rewards,
+      transactions,
+      actors: block.actors.iter().map(|(hash, actor, role)| (hash.to_string(), actor.to_string(), role.as_str())).collect(),
+      owners: block.owners.iter().map(|(hash, gateway, owner)| (hash.to_string(), gateway.to_string(), owner.to_string())).collect(),
+      prices: block.prices.iter().map(|(hash, public_key, price)| (hash.to_string(), public_key.to_string(), to_i64(*price))).collect(),
+    })
+  }
+}
+
+impl SqliteSink {
+  pub fn open(path: &str) -> Result<Self> {
+    let conn = Connection::open(path)?;
+    conn.execute_batch(SCHEMA)?;
+    Ok(Self { conn: Arc::new(Mutex::new(conn)) })
+  }
+
+  // Run `f` with the connection on the blocking thread pool.
+  async fn with_conn<T, F>(&self, f: F) -> Result<T>
+  where
+    T: Send + 'static,
+    F: FnOnce(&mut Connection) -> Result<T> + Send + 'static,
+  {
+    let conn = self.conn.clone();
+    sink::blocking(move || f(&mut conn.lock().unwrap())).await
+  }
+}
+
+#[async_trait]
+impl Sink for SqliteSink {
+  async fn position(&mut self) -> Result<Option<Position>> {
+    self.with_conn(|conn| {
+      conn.query_row("SELECT height, first_block, hash FROM follower_info", params![], |r| {
+        Ok(Position {
+          height: u64::try_from(r.get::<_, i64>(0)?).unwrap(),
+          first_block: u64::try_from(r.get::<_, i64>(1)?).unwrap(),
+          hash: r.get(2)?,
+        })
+      })
+      .optional()
+      .map_err(|e| e.into())
+    }).await
+  }
+
+  async fn start(&mut self, first_block: u64) -> Result<()> {
+    self.with_conn(move |conn| {
+      // nothing is loaded yet, the first block to load is first_block
+      conn.execute("INSERT INTO follower_info (height, first_block) VALUES (?1, ?2)",
+        params![to_i64(first_block - 1), to_i64(first_block)])?;
+      Ok(())
+    }).await
+  }
+
+  async fn write_block(&mut self, block: &Block<'_>) -> Result<()> {
+    let rows = Rows::new(block)?;
+    let reward_count = to_i64(block.rewards.len() as u64);
+    self.with_conn(move |conn| {
+      let height = rows.height;
+      let tx = conn.transaction()?;
+      {
+        let mut stmt = tx.prepare_cached("INSERT INTO rewards (block, transaction_hash, time, account, gateway, amount, type)
+          VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)")?;
+        for (account, gateway, amount, r#type) in &rows.rewards {
+          stmt.execute(params![height, rows.hash, rows.time, account, gateway, amount, r#type])?;
+        }
+      }
+      for (hash, r#type, fields) in &rows.transactions {
+        tx.execute("INSERT INTO transactions (block, hash, type, fields) VALUES (?1, ?2, ?3, ?4)",
+          params![height, hash, r#type, fields])?;
+      }
+      for (hash, actor, role) in &rows.actors {
+        tx.execute("INSERT OR IGNORE INTO transaction_actors (actor, actor_role, transaction_hash, block) VALUES (?1, ?2, ?3, ?4)",
+          params![actor, role, hash, height])?;
+      }
+      for (hash, gateway, owner) in &rows.owners {
+        tx.execute("INSERT INTO gateway_owners (block, transaction_hash, gateway, owner) VALUES (?1, ?2, ?3, ?4)",
+          params![height, hash, gateway, owner])?;
+      }
+      for (hash, public_key, price) in &rows.prices {
+        tx.execute("INSERT INTO oracle_prices (block, transaction_hash, public_key, price, time) VALUES (?1, ?2, ?3, ?4, ?5)",
+          params![height, hash, public_key, price, rows.time])?;
+      }
+      tx.execute("INSERT INTO blocks (height, hash, prev_hash, time, transaction_count, reward_count)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![height, rows.hash, rows.prev_hash, rows.time, rows.transaction_count, reward_count])?;
+      tx.execute("UPDATE follower_info SET height = ?1, hash = ?2", params![height, rows.hash])?;
+      tx.commit()?;
+      Ok(())
+    }).await
+  }
+
+  async fn block_hash(&mut self, height: u64) -> Result<Option<String>> {
+    self.with_conn(move |conn| {
+      conn.query_row("SELECT hash FROM blocks WHERE height = ?1", params![to_i64(height)], |r| r.get(0))
+        .optional()
+        .map_err(|e| e.into())
+    }).await
+  }
+
+  async fn rollback(&mut self, height: u64) -> Result<Option<String>> {
+    let h = to_i64(height);
+    self.with_conn(move |conn| {
+      let tx = conn.transaction()?;
+      for (table, column) in BLOCK_TABLES {
+        tx.execute(format!("DELETE FROM {} WHERE {} > ?1", table, column).as_str(), params![h])?;
+      }
+      let hash: Option<String> = tx.query_row("SELECT hash FROM blocks WHERE height = ?1", params![h], |r| r.get(0))
+        .optional()?;
+      tx.execute("UPDATE follower_info SET height = ?1, hash = ?2", params![h, hash])?;
+      tx.commit()?;
+      Ok(hash)
+    }).await
+  }
+}
+
+fn to_i64(v: u64) -> i64 {
+  i64::try_from(v).unwrap()
+}
@@ -17,7 +17,7 @@ pub async fn add_transaction<'a>(pgtran: &'a PgTransaction<'a>,
   block: u64, 
   hash: String, 
   r#type: &str, 
-  transaction: Transaction) -> Result<Vec<tokio_postgres::Row>> {
+  transaction: &Transaction) -> Result<Vec<tokio_postgres::Row>> {
   let stmt = prepare(&pgtran).await.unwrap();
   let fields = Json(transaction);
 
@@ -0,0 +1,126 @@
+use crate::*;
+use tokio_postgres::{ Transaction as PgTransaction };
+use helium_jsonrpc::Transaction;
+use std::convert::TryFrom;
+
+/// Load the typed columns of the transaction types we normalize into their own tables.
+/// Every other type is only stored as jsonb in `transactions`.
+pub async fn add_typed_transaction<'a>(pgtran: &'a PgTransaction<'a>,
+  block: u64,
+  hash: &str,
+  r#type: &str,
+  transaction: &Transaction) -> Result<()> {
+  let block = to_i64(block);
+  match transaction {
+    Transaction::PaymentV1(p) => {
+      add_payment(pgtran, block, hash, r#type, &p.payer, &p.payee, bones(p.amount)?, p.fee, p.nonce, None).await?;
+    },
+    Transaction::PaymentV2(p) => {
+      for payment in &p.payments {
+        add_payment(pgtran, block, hash, r#type, &p.payer, &payment.payee, bones(payment.amount)?, p.fee, p.nonce, payment.memo.as_deref()).await?;
+      }
+    },
+    Transaction::AddGatewayV1(g) => {
+      pgtran.execute("INSERT INTO add_gateways (block, hash, owner, gateway, payer, fee, staking_fee)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)",
+        &[&block, &hash, &g.owner, &g.gateway, &g.payer, &to_i64(g.fee), &to_i64(g.staking_fee)]).await?;
+    },
+    Transaction::AssertLocationV1(a) => {
+      add_assert_location(pgtran, block, hash, r#type, &a.owner, &a.gateway, &a.payer, &a.location,
+        a.nonce, a.fee, a.staking_fee, None, None).await?;
+    },
+    Transaction::AssertLocationV2(a) => {
+      add_assert_location(pgtran, block, hash, r#type, &a.owner, &a.gateway, &a.payer, &a.location,
+        a.nonce, a.fee, a.staking_fee, Some(a.gain), Some(a.elevation)).await?;
+    },
+    Transaction::TransferHotspotV1(t) => {
+      add_transfer_hotspot(pgtran, block, hash, r#type, &t.gateway, &t.seller, &t.buyer, Some(bones(t.amount_to_seller)?), t.fee).await?;
+    },
+    Transaction::TransferHotspotV2(t) => {
+      add_transfer_hotspot(pgtran, block, hash, r#type, &t.gateway, &t.owner, &t.new_owner, None, t.fee).await?;
+    },
+    Transaction::StateChannelCloseV1(sc) => {
+      for summary in &sc.state_channel.summaries {
+        pgtran.execute("INSERT INTO state_channel_closes (block, hash, closer, state_channel_id, owner, gateway, num_dcs, num_packets, fee)
+          VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)",
+          &[&block, &hash, &sc.closer, &sc.state_channel.id, &sc.state_channel.owner, &summary.client,
+            &to_i64(summary.num_dcs), &to_i64(summary.num_packets), &to_i64(sc.fee)]).await?;
+      }
+    },
+    Transaction::PocReceiptsV1(poc) => {
+      for (position, element) in poc.path.iter().enumerate() {
+        pgtran.execute("INSERT INTO poc_receipts (block, hash, challenger, challengee, path_position, witnesses, fee)
+          VALUES ($1, $2, $3, $4, $5, $6, $7)",
+          &[&block, &hash, &poc.challenger, &element.challengee,
+            &i32::try_from(position).unwrap(), &i32::try_from(element.witnesses.len()).unwrap(), &to_i64(poc.fee)]).await?;
+      }
+    },
+    _ => (),
+  }
+  Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn add_payment<'a>(pgtran: &'a PgTransaction<'a>,
+  block: i64,
+  hash: &str,
+  r#type: &str,
+  payer: &str,
+  payee: &str,
+  amount: i64,
+  fee: u64,
+  nonce: u64,
+  memo: Option<&str>) -> Result<u64> {
+  pgtran.execute(r#"INSERT INTO payments (block, hash, type, payer, payee, amount, fee, nonce, memo)
+    VALUES ($1, $2, CAST(CAST($3 AS VARCHAR) AS "transaction_type"), $4, $5, $6, $7, $8, $9)"#,
+    &[&block, &hash, &r#type, &payer, &payee, &amount, &to_i64(fee), &to_i64(nonce), &memo])
+    .await
+    .map_err(|e| error::Error::PgError(e))
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn add_assert_location<'a>(pgtran: &'a PgTransaction<'a>,
+  block: i64,
+  hash: &str,
+  r#type: &str,
+  owner: &str,
+  gateway: &str,
+  payer: &Option<String>,
+  location: &str,
+  nonce: u64,
+  fee: u64,
+  staking_fee: u64,
+  gain: Option<i32>,
+  elevation: Option<i32>) -> Result<u64> {
+  pgtran.execute(r#"INSERT INTO assert_locations (block, hash, type, owner, gateway, payer, location, nonce, fee, staking_fee, gain, elevation)
+    VALUES ($1, $2, CAST(CAST($3 AS VARCHAR) AS "transaction_type"), $4, $5, $6, $7, $8, $9, $10, $11, $12)"#,
+    &[&block, &hash, &r#type, &owner, &gateway, payer, &location, &to_i64(nonce), &to_i64(fee), &to_i64(staking_fee), &gain, &elevation])
+    .await
+    .map_err(|e| error::Error::PgError(e))
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn add_transfer_hotspot<'a>(pgtran: &'a PgTransaction<'a>,
+  block: i64,
+  hash: &str,
+  r#type: &str,
+  gateway: &str,
+  seller: &str,
+  buyer: &str,
+  amount_to_seller: Option<i64>,
+  fee: u64) -> Result<u64> {
+  pgtran.execute(r#"INSERT INTO transfer_hotspots (block, hash, type, gateway, seller, buyer, amount_to_seller, fee)
+    VALUES ($1, $2, CAST(CAST($3 AS VARCHAR) AS "transaction_type"), $4, $5, $6, $7, $8)"#,
+    &[&block, &hash, &r#type, &gateway, &seller, &buyer, &amount_to_seller, &to_i64(fee)])
+    .await
+    .map_err(|e| error::Error::PgError(e))
+}
+
+fn bones<T: Into<u64>>(amount: T) -> Result<i64> {
+  let amount = amount.into();
+  i64::try_from(amount).map_err(|e| Error::Custom(format!("failed to convert amount {} to i64: {}", amount, e.to_string())))
+}
+
+fn to_i64(v: u64) -> i64 {
+  i64::try_from(v).unwrap()
+}
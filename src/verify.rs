@@ -0,0 +1,75 @@
+use crate::*;
+use tokio_postgres::{ Client as PgClient };
+use std::{convert::TryFrom, fmt};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GapKind {
+  // no row in `blocks` for this height
+  Missing,
+  // the block was recorded but fewer rewards were stored than it declared
+  PartialRewards,
+  // the block was recorded but not all of its transactions were stored (Full mode)
+  PartialTransactions,
+}
+
+#[derive(Debug)]
+pub struct Gap {
+  pub height: u64,
+  pub kind: GapKind,
+}
+
+impl fmt::Display for Gap {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let kind = match self.kind {
+      GapKind::Missing => "missing",
+      GapKind::PartialRewards => "partial rewards",
+      GapKind::PartialTransactions => "partial transactions",
+    };
+    write!(f, "{}: {}", self.height, kind)
+  }
+}
+
+/// Scan every height from where blocks were first recorded (follower_info's `first_block`,
+/// or `blocks_from` for databases loaded before then) up to `height` for blocks that were
+/// never loaded or were only partially loaded. Gaps are returned in height order.
+pub async fn find_gaps(pgclient: &PgClient, mode: EtlMode) -> Result<Vec<Gap>> {
+  let rows = pgclient.query("SELECT GREATEST(first_block, COALESCE(blocks_from, first_block)), height FROM follower_info", &[]).await?;
+  let (first_block, height): (i64, i64) = match rows.first() {
+    Some(r) => (r.get(0), r.get(1)),
+    None => return Err(error::Error::Custom("no follower info".to_string())),
+  };
+
+  let mut gaps = vec!();
+
+  let missing = pgclient.query("SELECT h FROM generate_series($1::bigint, $2::bigint) AS h
+    WHERE NOT EXISTS (SELECT 1 FROM blocks WHERE blocks.height = h)", &[&first_block, &height]).await?;
+  gaps.extend(missing.iter().map(|r| gap(r.get(0), GapKind::Missing)));
+
+  let partial_rewards = pgclient.query("SELECT b.height FROM blocks b
+    LEFT JOIN (SELECT block, count(*) AS n FROM rewards WHERE block BETWEEN $1 AND $2 GROUP BY block) r
+      ON r.block = b.height
+    WHERE b.height BETWEEN $1 AND $2
+      AND b.reward_count IS NOT NULL
+      AND b.reward_count <> COALESCE(r.n, 0)", &[&first_block, &height]).await?;
+  gaps.extend(partial_rewards.iter().map(|r| gap(r.get(0), GapKind::PartialRewards)));
+
+  if let EtlMode::Full = mode {
+    let partial_transactions = pgclient.query("SELECT b.height FROM blocks b
+      LEFT JOIN (SELECT block, count(*) AS n FROM transactions WHERE block BETWEEN $1 AND $2 GROUP BY block) t
+        ON t.block = b.height
+      WHERE b.height BETWEEN $1 AND $2
+        AND b.transaction_count IS NOT NULL
+        AND b.transaction_count <> COALESCE(t.n, 0)", &[&first_block, &height]).await?;
+    gaps.extend(partial_transactions.iter().map(|r| gap(r.get(0), GapKind::PartialTransactions)));
+  }
+
+  gaps.sort_by_key(|g| g.height);
+  Ok(gaps)
+}
+
+fn gap(height: i64, kind: GapKind) -> Gap {
+  Gap {
+    height: u64::try_from(height).unwrap(),
+    kind,
+  }
+}
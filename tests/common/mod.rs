@@ -0,0 +1,139 @@
+//! Test harness: a mock blockchain-node serving JSON-RPC responses from fixture files,
+//! and helpers for running the follower against a scratch Postgres database.
+#![allow(dead_code)]
+
+use helium_etl_lite::{db, migrate, settings::Settings, sink::{PostgresSink, Sink}};
+use hyper::{
+  service::{make_service_fn, service_fn},
+  Body, Request, Response, Server,
+};
+use serde_json::{json, Value};
+use std::{convert::Infallible, env, fs, net::SocketAddr, path::{Path, PathBuf}};
+use tokio_postgres::{Client as PgClient, NoTls};
+
+/// The database the ignored Postgres tests run against. It is wiped by every test.
+pub const DATABASE_URL_VAR: &str = "HELIUM_ETL_TEST_DATABASE_URL";
+
+lazy_static::lazy_static! {
+  // tests sharing the database run one at a time
+  pub static ref DATABASE_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::new(());
+}
+
+/// Serves `block_height`, `block_get` and `transaction_get` from `tests/fixtures/<name>`:
+/// `blocks/<height>.json` holds a block, `transactions/<hash>.json` a transaction, and
+/// the node height is the highest block fixture.
+pub struct MockNode {
+  pub addr: SocketAddr,
+}
+
+impl MockNode {
+  pub async fn start(name: &str) -> MockNode {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name);
+    let make_svc = make_service_fn(move |_| {
+      let dir = dir.clone();
+      async move {
+        Ok::<_, Infallible>(service_fn(move |req| handle(dir.clone(), req)))
+      }
+    });
+    let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_svc);
+    let addr = server.local_addr();
+    tokio::spawn(server);
+    MockNode { addr }
+  }
+
+  pub fn url(&self) -> String {
+    format!("http://{}", self.addr)
+  }
+}
+
+async fn handle(dir: PathBuf, req: Request<Body>) -> Result<Response<Body>, Infallible> {
+  let body = hyper::body::to_bytes(req.into_body()).await.unwrap_or_default();
+  let request: Value = serde_json::from_slice(&body).unwrap_or(Value::Null);
+  let result = match request["method"].as_str() {
+    Some("block_height") => Some(json!(node_height(&dir))),
+    Some("block_get") => request["params"]["height"].as_u64()
+      .and_then(|h| fixture(&dir.join("blocks").join(format!("{}.json", h)))),
+    Some("transaction_get") => request["params"]["hash"].as_str()
+      .and_then(|h| fixture(&dir.join("transactions").join(format!("{}.json", h)))),
+    _ => None,
+  };
+  let response = match result {
+    Some(r) => json!({"jsonrpc": "2.0", "id": request["id"], "result": r}),
+    None => json!({"jsonrpc": "2.0", "id": request["id"], "error": {"code": -100, "message": "not_found"}}),
+  };
+  Ok(Response::new(Body::from(response.to_string())))
+}
+
+fn fixture(path: &Path) -> Option<Value> {
+  let contents = fs::read_to_string(path).ok()?;
+  Some(serde_json::from_str(&contents).expect("invalid fixture"))
+}
+
+pub fn node_height(dir: &Path) -> u64 {
+  fs::read_dir(dir.join("blocks"))
+    .expect("fixture blocks")
+    .filter_map(|e| e.ok()?.path().file_stem()?.to_str()?.parse().ok())
+    .max()
+    .unwrap_or(0)
+}
+
+/// Settings pointing at the mock node and the test database.
+pub fn settings(node: &MockNode, database_url: &str, mode: &str) -> Settings {
+  write_settings(node, database_url, mode, "")
+}
+
+/// Settings pointing at the mock node and writing to a file sink, with `sink` as the
+/// `[sink]` section's contents.
+pub fn sink_settings(node: &MockNode, mode: &str, sink: &str) -> Settings {
+  write_settings(node, "", mode, &format!("[sink]\n{}\n", sink))
+}
+
+fn write_settings(node: &MockNode, database_url: &str, mode: &str, extra: &str) -> Settings {
+  let path = env::temp_dir().join(format!("etl_lite_test_{}_{}.toml", mode, node.addr.port()));
+  fs::write(&path, format!(r#"
+node_addr = "{}"
+database_url = "{}"
+mode = "{}"
+backfill = "false"
+
+{}
+[log]
+log_dir = "{}"
+"#, node.url(), database_url, mode, extra, env::temp_dir().join("etl_lite_test_log").display())).unwrap();
+  Settings::new(&path).unwrap()
+}
+
+/// An empty scratch directory for `name` under the system temp dir.
+pub fn scratch_dir(name: &str) -> PathBuf {
+  let dir = env::temp_dir().join(format!("etl_lite_test_{}", name));
+  let _ = fs::remove_dir_all(&dir);
+  fs::create_dir_all(&dir).unwrap();
+  dir
+}
+
+/// The test database url. Panics when it isn't set, so the Postgres tests can't pass
+/// without running.
+pub fn database_url() -> String {
+  match env::var(DATABASE_URL_VAR) {
+    Ok(url) => url,
+    Err(_) => panic!("{} must point at a scratch database to run the Postgres tests", DATABASE_URL_VAR),
+  }
+}
+
+/// Wipe the test database, run migrations and start following at `first_block`.
+pub async fn reset_database(settings: &Settings, first_block: u64) -> PgClient {
+  let (client, connection) = tokio_postgres::connect(&settings.database_url, NoTls).await.unwrap();
+  tokio::spawn(connection);
+  client.batch_execute("DROP SCHEMA public CASCADE; CREATE SCHEMA public;").await.unwrap();
+  migrate::run(settings).await;
+  PostgresSink::new(db::pool(settings).unwrap(), &logger()).start(first_block).await.unwrap();
+  client
+}
+
+pub fn logger() -> slog::Logger {
+  slog::Logger::root(slog::Discard, slog::o!())
+}
+
+pub async fn count(client: &PgClient, table: &str) -> i64 {
+  client.query_one(format!("SELECT count(*) FROM {}", table).as_str(), &[]).await.unwrap().get(0)
+}
@@ -0,0 +1,138 @@
+mod common;
+
+use common::MockNode;
+use helium_etl_lite::{db, follower::Follower, settings::Settings, sink};
+
+// End-to-end runs of the follower from the mock node into Postgres. Ignored by default,
+// run them with HELIUM_ETL_TEST_DATABASE_URL set and `cargo test -- --ignored`.
+
+/// Run a follower until it has caught up with the node, returning its height.
+async fn run_follower(settings: &Settings) -> u64 {
+  let (_trigger, shutdown) = triggered::trigger();
+  let pool = db::pool(settings).unwrap();
+  let sink = sink::from_settings(settings, Some(pool.clone()), &common::logger()).unwrap();
+  let mut follower = Follower::new(settings, sink, Some(pool), &common::logger(), shutdown).await.unwrap();
+  follower.run().await;
+  follower.height
+}
+
+/// Follow the `node` fixtures from block 1, with `filters` as (type, value) pairs
+/// that are already fully backfilled.
+async fn follow(mode: &str, filters: &[(&str, &str)]) -> tokio_postgres::Client {
+  let node = MockNode::start("node").await;
+  let settings = common::settings(&node, &common::database_url(), mode);
+  let client = common::reset_database(&settings, 1).await;
+  for (filter_type, value) in filters {
+    client.execute("INSERT INTO filters (type, value, added_height, backfill_height) VALUES ($1::text::filter_type, $2, 0, 0)",
+      &[filter_type, value]).await.unwrap();
+  }
+
+  assert_eq!(run_follower(&settings).await, 3);
+  client
+}
+
+#[tokio::test]
+#[ignore]
+async fn rewards_mode_loads_rewards_and_blocks() {
+  let _lock = common::DATABASE_LOCK.lock().await;
+  let client = follow("rewards", &[]).await;
+
+  let info = client.query_one("SELECT height, first_block, hash FROM follower_info", &[]).await.unwrap();
+  assert_eq!(info.get::<_, i64>("height"), 3);
+  assert_eq!(info.get::<_, i64>("first_block"), 1);
+  assert_eq!(info.get::<_, String>("hash"), "block3hash");
+
+  assert_eq!(common::count(&client, "blocks").await, 3);
+  assert_eq!(common::count(&client, "transactions").await, 0);
+
+  let rewards = client.query("SELECT block, transaction_hash, account, gateway, amount, type FROM rewards ORDER BY amount", &[])
+    .await
+    .unwrap();
+  assert_eq!(rewards.len(), 2);
+  assert_eq!(rewards[0].get::<_, i64>("block"), 2);
+  // rewards are keyed by the hash of the block they were paid in
+  assert_eq!(rewards[0].get::<_, String>("transaction_hash"), "block2hash");
+  assert_eq!(rewards[0].get::<_, String>("account"), "account1");
+  assert_eq!(rewards[0].get::<_, String>("gateway"), "gateway1");
+  assert_eq!(rewards[0].get::<_, i64>("amount"), 1000);
+  assert_eq!(rewards[0].get::<_, String>("type"), "poc_witnesses");
+  assert_eq!(rewards[1].get::<_, i64>("amount"), 2500);
+
+  let reward_count: i32 = client.query_one("SELECT reward_count FROM blocks WHERE height = 2", &[]).await.unwrap().get(0);
+  assert_eq!(reward_count, 2);
+}
+
+#[tokio::test]
+#[ignore]
+async fn full_mode_loads_transactions() {
+  let _lock = common::DATABASE_LOCK.lock().await;
+  let client = follow("full", &[]).await;
+
+  assert_eq!(common::count(&client, "rewards").await, 2);
+
+  let transactions = client.query("SELECT block, hash, type::varchar(255) FROM transactions ORDER BY block", &[])
+    .await
+    .unwrap();
+  assert_eq!(transactions.len(), 2);
+  assert_eq!(transactions[0].get::<_, String>("hash"), "rewardshash");
+  assert_eq!(transactions[1].get::<_, i64>("block"), 3);
+  assert_eq!(transactions[1].get::<_, String>("hash"), "paymenthash");
+  assert_eq!(transactions[1].get::<_, String>("type"), "payment_v1");
+
+  let payment = client.query_one("SELECT payer, payee, amount FROM payments", &[]).await.unwrap();
+  assert_eq!(payment.get::<_, String>("payer"), "account1");
+  assert_eq!(payment.get::<_, String>("payee"), "account2");
+  assert_eq!(payment.get::<_, i64>("amount"), 100000000);
+
+  assert_eq!(common::count(&client, "transaction_actors").await, 2);
+}
+
+#[tokio::test]
+#[ignore]
+async fn filters_mode_loads_only_matching_rewards_and_transactions() {
+  let _lock = common::DATABASE_LOCK.lock().await;
+  let client = follow("filters", &[("account", "account1")]).await;
+
+  let rewards = client.query("SELECT account, amount FROM rewards", &[]).await.unwrap();
+  assert_eq!(rewards.len(), 1);
+  assert_eq!(rewards[0].get::<_, String>("account"), "account1");
+  assert_eq!(rewards[0].get::<_, i64>("amount"), 1000);
+
+  let transactions = client.query("SELECT hash FROM transactions", &[]).await.unwrap();
+  assert_eq!(transactions.len(), 1);
+  assert_eq!(transactions[0].get::<_, String>("hash"), "paymenthash");
+
+  // every block is recorded, matching or not
+  assert_eq!(common::count(&client, "blocks").await, 3);
+}
+
+#[tokio::test]
+#[ignore]
+async fn reorg_rolls_back_to_the_fork_point() {
+  let _lock = common::DATABASE_LOCK.lock().await;
+  let client = follow("rewards", &[]).await;
+
+  // the `reorg` fixtures share block 1 with `node` and fork from block 2 onwards
+  let node = MockNode::start("reorg").await;
+  let settings = common::settings(&node, &common::database_url(), "rewards");
+  assert_eq!(run_follower(&settings).await, 4);
+
+  let info = client.query_one("SELECT height, hash FROM follower_info", &[]).await.unwrap();
+  assert_eq!(info.get::<_, i64>("height"), 4);
+  assert_eq!(info.get::<_, String>("hash"), "block4bhash");
+
+  let hashes: Vec<String> = client.query("SELECT hash FROM blocks ORDER BY height", &[])
+    .await
+    .unwrap()
+    .iter()
+    .map(|r| r.get("hash"))
+    .collect();
+  assert_eq!(hashes, vec!("block1hash", "block2bhash", "block3bhash", "block4bhash"));
+
+  let rewards = client.query("SELECT block, transaction_hash, account, amount FROM rewards", &[]).await.unwrap();
+  assert_eq!(rewards.len(), 1);
+  assert_eq!(rewards[0].get::<_, i64>("block"), 2);
+  assert_eq!(rewards[0].get::<_, String>("transaction_hash"), "block2bhash");
+  assert_eq!(rewards[0].get::<_, String>("account"), "account3");
+  assert_eq!(rewards[0].get::<_, i64>("amount"), 3000);
+}
@@ -0,0 +1,34 @@
+mod common;
+
+use common::MockNode;
+use helium_etl_lite::rpc::NodeClient;
+use helium_jsonrpc::Transaction;
+
+// These only need the mock node, not Postgres.
+
+#[tokio::test]
+async fn serves_height_blocks_and_transactions() {
+  let node = MockNode::start("node").await;
+  let settings = common::settings(&node, "postgresql://unused@127.0.0.1/unused", "rewards");
+  let client = NodeClient::new(&settings);
+
+  assert_eq!(client.height().await.unwrap(), 3);
+
+  let block = client.get_block(2).await.unwrap();
+  assert_eq!(block.height, 2);
+  assert_eq!(block.prev_hash, "block1hash");
+  assert_eq!(block.transactions.len(), 1);
+  assert_eq!(block.transactions[0].r#type, "rewards_v2");
+
+  match client.get_transaction(&block.transactions[0].hash).await.unwrap() {
+    Transaction::RewardsV2(rewards) => assert_eq!(rewards.rewards.len(), 2),
+    other => panic!("expected rewards_v2, got {:?}", other),
+  }
+}
+
+#[tokio::test]
+async fn missing_block_is_an_error() {
+  let node = MockNode::start("node").await;
+  let settings = common::settings(&node, "postgresql://unused@127.0.0.1/unused", "rewards");
+  assert!(NodeClient::new(&settings).get_block(4).await.is_err());
+}
@@ -0,0 +1,104 @@
+mod common;
+
+use common::MockNode;
+use helium_etl_lite::{follower::Follower, settings::Settings, sink::{self, Sink}};
+use rusqlite::{params, Connection};
+use std::fs;
+
+// The file sinks, run end-to-end from the mock node. These don't need Postgres.
+
+async fn open(settings: &Settings) -> Box<dyn Sink> {
+  sink::from_settings(settings, None, &common::logger()).unwrap()
+}
+
+/// Run a follower into the sink until it has caught up with the node, returning its height.
+async fn run_follower(settings: &Settings) -> u64 {
+  let (_trigger, shutdown) = triggered::trigger();
+  let mut follower = Follower::new(settings, open(settings).await, None, &common::logger(), shutdown).await.unwrap();
+  follower.run().await;
+  follower.height
+}
+
+async fn assert_position(sink: &mut dyn Sink, height: u64, hash: Option<&str>) {
+  let position = sink.position().await.unwrap().unwrap();
+  assert_eq!(position.height, height);
+  assert_eq!(position.hash.as_deref(), hash);
+  assert_eq!(position.first_block, 1);
+}
+
+/// Follow the `node` fixtures from block 1, then the `reorg` fixtures that fork after
+/// block 1, then roll back to block 2 by hand. `sink` is the `[sink]` section.
+async fn follow_reorg_and_roll_back(sink: &str) {
+  let node = MockNode::start("node").await;
+  let settings = common::sink_settings(&node, "full", sink);
+  let mut s = open(&settings).await;
+  assert!(s.position().await.unwrap().is_none());
+  s.start(1).await.unwrap();
+  assert_position(s.as_mut(), 0, None).await;
+  drop(s);
+
+  assert_eq!(run_follower(&settings).await, 3);
+  let mut s = open(&settings).await;
+  assert_position(s.as_mut(), 3, Some("block3hash")).await;
+  assert_eq!(s.block_hash(2).await.unwrap().as_deref(), Some("block2hash"));
+  assert_eq!(s.block_hash(4).await.unwrap(), None);
+  drop(s);
+
+  // the follower finds the fork at block 1, rolls back and loads the node's chain
+  let reorg = MockNode::start("reorg").await;
+  let settings = common::sink_settings(&reorg, "full", sink);
+  assert_eq!(run_follower(&settings).await, 4);
+  let mut s = open(&settings).await;
+  assert_position(s.as_mut(), 4, Some("block4bhash")).await;
+  assert_eq!(s.block_hash(1).await.unwrap().as_deref(), Some("block1hash"));
+  assert_eq!(s.block_hash(2).await.unwrap().as_deref(), Some("block2bhash"));
+
+  assert_eq!(s.rollback(2).await.unwrap().as_deref(), Some("block2bhash"));
+  assert_position(s.as_mut(), 2, Some("block2bhash")).await;
+  assert_eq!(s.block_hash(3).await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn sqlite_sink_writes_and_rolls_back_blocks() {
+  let path = common::scratch_dir("sqlite").join("etl_lite.sqlite");
+  follow_reorg_and_roll_back(&format!("type = \"sqlite\"\npath = \"{}\"", path.display())).await;
+
+  let conn = Connection::open(&path).unwrap();
+  let count = |table: &str| -> i64 {
+    conn.query_row(format!("SELECT count(*) FROM {}", table).as_str(), params![], |r| r.get(0)).unwrap()
+  };
+  assert_eq!(count("blocks"), 2);
+  assert_eq!(count("transactions"), 1);
+
+  let (block, hash, account, amount): (i64, String, String, i64) = conn.query_row(
+    "SELECT block, transaction_hash, account, amount FROM rewards", params![],
+    |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?, r.get(3)?))).unwrap();
+  assert_eq!((block, hash.as_str(), account.as_str(), amount), (2, "block2bhash", "account3", 3000));
+}
+
+#[tokio::test]
+async fn ndjson_sink_writes_and_rolls_back_blocks() {
+  let dir = common::scratch_dir("ndjson");
+  follow_reorg_and_roll_back(&format!("type = \"ndjson\"\npath = \"{}\"", dir.display())).await;
+
+  let lines: Vec<serde_json::Value> = fs::read_to_string(dir.join("etl_lite.ndjson"))
+    .unwrap()
+    .lines()
+    .map(|l| serde_json::from_str(l).unwrap())
+    .collect();
+  let kinds = |kind: &str| -> Vec<&serde_json::Value> {
+    lines.iter().filter(|l| l["kind"] == kind).collect()
+  };
+
+  // three blocks from `node`, then three more from `reorg` after rolling back to block 1
+  let heights: Vec<u64> = kinds("block").iter().map(|l| l["height"].as_u64().unwrap()).collect();
+  assert_eq!(heights, vec!(1, 2, 3, 2, 3, 4));
+  let rollbacks: Vec<u64> = kinds("rollback").iter().map(|l| l["height"].as_u64().unwrap()).collect();
+  assert_eq!(rollbacks, vec!(1, 2));
+  assert_eq!(lines.last().unwrap()["kind"], "rollback");
+
+  let rewards = kinds("reward");
+  assert_eq!(rewards.len(), 3);
+  assert_eq!(rewards[2]["account"], "account3");
+  assert_eq!(rewards[2]["transaction_hash"], "block2bhash");
+}